@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Largest datagram the server accepts; a sealed, tagged `State` with a full
+/// event history is well under this.
+pub const MAX_PACKET_LEN: usize = 1200;
+/// Lobby commands and handshakes per second from one IP, and the burst allowed.
+const COMMAND_RATE: f64 = 4.0;
+const COMMAND_BURST: f64 = 16.0;
+/// Rooms one IP may open per second, and the burst allowed.
+const CREATE_RATE: f64 = 0.1;
+const CREATE_BURST: f64 = 3.0;
+/// How often the receive loop forgets quiet IPs.
+pub const SWEEP_INTERVAL: Duration = Duration::from_secs(30);
+/// Bytes per second a room may relay, and the burst allowed: a relayed match
+/// at 60 Hz in both directions needs well under half of it.
+pub const ROOM_RELAY_RATE: f64 = 128.0 * 1024.0;
+pub const ROOM_RELAY_BURST: f64 = 32.0 * 1024.0;
+
+/// `rate` tokens a second, saved up to `burst`.
+pub struct TokenBucket {
+    tokens: f64,
+    rate:   f64,
+    burst:  f64,
+    last:   Instant,
+}
+
+impl TokenBucket {
+    /// Starts full.
+    pub fn new(rate: f64, burst: f64) -> Self {
+        Self { tokens: burst, rate, burst, last: Instant::now() }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        self.tokens = (self.tokens + now.duration_since(self.last).as_secs_f64() * self.rate).min(self.burst);
+        self.last = now;
+    }
+
+    /// Takes `n` tokens if there are that many.
+    pub fn take(&mut self, n: f64) -> bool {
+        self.refill();
+        if self.tokens < n {
+            return false;
+        }
+        self.tokens -= n;
+        true
+    }
+
+    fn is_full(&mut self) -> bool {
+        self.refill();
+        self.tokens >= self.burst
+    }
+}
+
+/// Traffic dropped since startup, by reason.
+#[derive(Default)]
+pub struct Drops {
+    pub oversize:   AtomicU64,
+    /// Commands and handshakes over an IP's rate.
+    pub commands:   AtomicU64,
+    /// `CREATE*` over an IP's rate.
+    pub creates:    AtomicU64,
+    /// Game traffic over its room's relay bandwidth.
+    pub relay:      AtomicU64,
+    /// Datagrams for a room whose inbox was full.
+    pub inbox_full: AtomicU64,
+    /// `Sealed` packets that did not open: no channel, forged, or replayed.
+    pub unopened:   AtomicU64,
+}
+
+impl Drops {
+    /// One line for the log, or `None` if nothing was dropped.
+    pub fn summary(&self) -> Option<String> {
+        let counts = [
+            (self.oversize.load(Ordering::Relaxed), "oversize"),
+            (self.commands.load(Ordering::Relaxed), "over command rate"),
+            (self.creates.load(Ordering::Relaxed), "over create rate"),
+            (self.relay.load(Ordering::Relaxed), "over room bandwidth"),
+            (self.inbox_full.load(Ordering::Relaxed), "inbox full"),
+            (self.unopened.load(Ordering::Relaxed), "sealed but unopenable"),
+        ];
+        let parts: Vec<String> = counts.iter().filter(|(n, _)| *n > 0).map(|(n, why)| format!("{} {}", n, why)).collect();
+        (!parts.is_empty()).then(|| format!("Dropped so far: {}", parts.join(", ")))
+    }
+}
+
+/// Command and room-creation buckets for one source IP.
+struct Source {
+    commands: TokenBucket,
+    creates:  TokenBucket,
+}
+
+/// Per-IP rate limits on what the receive loop handles itself. Keyed by IP
+/// rather than address, so a client cannot dodge them by switching ports.
+#[derive(Default)]
+pub struct SourceLimits {
+    sources: HashMap<IpAddr, Source>,
+}
+
+impl SourceLimits {
+    fn source(&mut self, ip: IpAddr) -> &mut Source {
+        self.sources.entry(ip.to_canonical()).or_insert_with(|| Source {
+            commands: TokenBucket::new(COMMAND_RATE, COMMAND_BURST),
+            creates:  TokenBucket::new(CREATE_RATE, CREATE_BURST),
+        })
+    }
+
+    /// A lobby command or handshake from `ip`.
+    pub fn command(&mut self, ip: IpAddr) -> bool {
+        self.source(ip).commands.take(1.0)
+    }
+
+    /// A `CREATE*` from `ip`, on top of its command budget.
+    pub fn create(&mut self, ip: IpAddr) -> bool {
+        self.source(ip).creates.take(1.0)
+    }
+
+    /// Forgets IPs whose buckets have refilled, i.e. that went quiet.
+    pub fn sweep(&mut self) {
+        self.sources.retain(|_, s| !(s.commands.is_full() && s.creates.is_full()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    async fn advance(secs: f64) {
+        tokio::time::advance(Duration::from_secs_f64(secs)).await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_bucket_refills_at_its_rate_up_to_its_burst() {
+        let mut bucket = TokenBucket::new(2.0, 3.0);
+        assert!((0..3).all(|_| bucket.take(1.0)));
+        assert!(!bucket.take(1.0));
+
+        advance(0.5).await;
+        assert!(bucket.take(1.0));
+        assert!(!bucket.take(1.0));
+
+        // However long it rests, it holds no more than the burst
+        advance(100.0).await;
+        assert!(bucket.is_full());
+        assert!(!bucket.take(4.0), "a refused take costs nothing");
+        assert!(bucket.take(3.0));
+        assert!(!bucket.take(1.0));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_sources_are_limited_separately() {
+        let (a, b) = (IpAddr::from([10, 0, 0, 1]), IpAddr::from([10, 0, 0, 2]));
+        let mut limits = SourceLimits::default();
+        assert_eq!((0..100).filter(|_| limits.command(a)).count(), COMMAND_BURST as usize);
+        assert_eq!((0..100).filter(|_| limits.create(a)).count(), CREATE_BURST as usize);
+        assert!(limits.command(b));
+        assert!(limits.create(b));
+
+        advance(1.0).await;
+        assert_eq!((0..100).filter(|_| limits.command(a)).count(), COMMAND_RATE as usize);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_ipv4_mapped_addresses_share_the_ipv4_budget() {
+        let v4 = Ipv4Addr::new(192, 0, 2, 7);
+        let mapped = IpAddr::V6(v4.to_ipv6_mapped());
+        let mut limits = SourceLimits::default();
+        assert!((0..COMMAND_BURST as usize).all(|_| limits.command(mapped)));
+        assert!(!limits.command(IpAddr::V4(v4)));
+        assert_eq!(limits.sources.len(), 1);
+        assert!(limits.command(IpAddr::V6(Ipv6Addr::LOCALHOST)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_sweep_forgets_only_quiet_sources() {
+        let (chatty, creator) = (IpAddr::from([10, 0, 0, 1]), IpAddr::from([10, 0, 0, 2]));
+        let mut limits = SourceLimits::default();
+        limits.command(chatty);
+        limits.create(creator);
+
+        // The command bucket is back to full, the create bucket is not
+        advance(1.0 / COMMAND_RATE).await;
+        limits.sweep();
+        assert!(!limits.sources.contains_key(&chatty));
+        assert!(limits.sources.contains_key(&creator));
+
+        advance(1.0 / CREATE_RATE).await;
+        limits.sweep();
+        assert!(limits.sources.is_empty());
+    }
+
+    #[test]
+    fn test_drops_summary_lists_only_what_was_dropped() {
+        let drops = Drops::default();
+        assert_eq!(drops.summary(), None);
+        drops.oversize.fetch_add(2, Ordering::Relaxed);
+        drops.inbox_full.fetch_add(1, Ordering::Relaxed);
+        drops.unopened.fetch_add(3, Ordering::Relaxed);
+        assert_eq!(
+            drops.summary().as_deref(),
+            Some("Dropped so far: 2 oversize, 1 inbox full, 3 sealed but unopenable")
+        );
+    }
+}
@@ -1,157 +1,158 @@
-use std::collections::HashMap;
-use std::net::SocketAddr;
-use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
 use std::time::Instant;
-use tokio::net::UdpSocket;
-use tokio::sync::Mutex;
 use tokio::time::Duration;
-use rand::Rng;
 
-const MAX_ROOMS: usize = 256;
-const ROOM_TIMEOUT_SECS: u64 = 120;
-
-type ClientMap = Arc<Mutex<HashMap<String, Room>>>;
-
-struct Room {
-    creator: SocketAddr,
-    joiner: Option<SocketAddr>,
-    created_at: Instant,
-    is_ai: bool,
-}
-
-fn generate_room_code() -> String {
-    format!("{:04}", rand::thread_rng().gen_range(0..10000))
-}
-
-// ─── Commands ────────────────────────────────────────────────────────────────
-
-async fn handle_command(socket: &Arc<UdpSocket>, rooms: &ClientMap, cmd: String, src: SocketAddr) {
-    let mut guard = rooms.lock().await;
-
-    if cmd.starts_with("CREATE_SOLO") {
-        if guard.len() >= MAX_ROOMS { return; }
-        let code = format!("SOLO_{}", rand::thread_rng().gen_range(1000..9999));
-        guard.insert(code.clone(), Room {
-            creator: src,
-            joiner: None,
-            created_at: Instant::now(),
-            is_ai: true,
-        });
-        let _ = socket.send_to(b"START", src).await;
-        println!("Solo game for {}", src);
-        return;
-    }
-
-    if cmd.starts_with("CREATE") {
-        if guard.len() >= MAX_ROOMS {
-            let _ = socket.send_to(b"BUSY", src).await;
-            return;
-        }
-        if guard.values().any(|r| r.creator == src) {
-            let _ = socket.send_to(b"ALREADY_HOSTING", src).await;
-            return;
-        }
-        let code = loop {
-            let c = generate_room_code();
-            if !guard.contains_key(&c) { break c; }
-        };
-        guard.insert(code.clone(), Room {
-            creator: src,
-            joiner: None,
-            created_at: Instant::now(),
-            is_ai: false,
-        });
-        let _ = socket.send_to(format!("CREATED:{}", code).as_bytes(), src).await;
-        println!("Room {} created by {}", code, src);
-        return;
-    }
-
-    if cmd.starts_with("JOIN:") {
-        let code = cmd[5..].trim().to_string();
-        if let Some(room) = guard.get_mut(&code) {
-            if room.joiner.is_some() {
-                let _ = socket.send_to(b"FULL", src).await;
-                return;
-            }
-            if room.creator == src {
-                let _ = socket.send_to(b"CANNOT_JOIN_OWN", src).await;
-                return;
-            }
-            room.joiner = Some(src);
-            let host = room.creator;
-
-            // Tell each player the other's public address (for P2P hole-punching)
-            let host_peer = format!("PEER:{}:{}", src.ip(), src.port());
-            let join_peer = format!("PEER:{}:{}", host.ip(), host.port());
-            let _ = socket.send_to(host_peer.as_bytes(), host).await;
-            let _ = socket.send_to(join_peer.as_bytes(), src).await;
-
-            let _ = socket.send_to(b"JOINED", src).await;
-            let _ = socket.send_to(b"START", host).await;
-            let _ = socket.send_to(b"START", src).await;
-            println!("Game started in room {}: {} vs {} (P2P capable)", code, host, src);
-            return;
-        }
-        let _ = socket.send_to(b"NOT_FOUND", src).await;
-    }
-}
+mod limit;
+mod room;
+mod sim;
+mod transport;
+use limit::{Drops, SourceLimits, MAX_PACKET_LEN, SWEEP_INTERVAL};
+use room::{Registry, RoomMsg, SharedRegistry, ROOM_TIMEOUT_SECS};
+use transport::Transport;
+use puckduel_core::protocol::{self, MessageType, ProtocolError};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let port = std::env::var("PORT").unwrap_or_else(|_| "9876".into());
     let addr = format!("[::]:{}", port);
-    let socket = Arc::new(UdpSocket::bind(&addr).await?);
-    println!("Game server listening on {} (relay mode)", addr);
-
-    let rooms: ClientMap = Arc::new(Mutex::new(HashMap::new()));
-
-    // Periodic cleanup of stale rooms
-    let cleanup_rooms = rooms.clone();
+    let socket = Arc::new(Transport::bind(&addr).await?);
+    println!("Game server listening on {} (relay + authoritative modes)", addr);
+
+    // Each room runs as its own task (see `room`); this loop only unwraps
+    // datagrams and hands them to the right room's inbox
+    let registry: SharedRegistry = Arc::new(Mutex::new(Registry::default()));
+    let drops = Arc::new(Drops::default());
+    let mut limits = SourceLimits::default();
+    let mut last_sweep = Instant::now();
+
+    // Periodic cleanup of idle encrypted channels, and a note of any dropped
+    // traffic; rooms close themselves
+    let cleanup_socket = socket.clone();
+    let cleanup_drops = drops.clone();
     tokio::spawn(async move {
+        let mut last_summary = None;
         loop {
             tokio::time::sleep(Duration::from_secs(30)).await;
-            let mut guard = cleanup_rooms.lock().await;
-            let before = guard.len();
-            guard.retain(|_, r| r.joiner.is_some() || r.is_ai || r.created_at.elapsed().as_secs() < ROOM_TIMEOUT_SECS);
-            let removed = before - guard.len();
-            if removed > 0 {
-                println!("Cleanup: removed {} stale room(s), {} remaining", removed, guard.len());
+            cleanup_socket.expire(Duration::from_secs(ROOM_TIMEOUT_SECS));
+            let summary = cleanup_drops.summary();
+            if summary != last_summary {
+                if let Some(line) = &summary {
+                    println!("{}", line);
+                }
+                last_summary = summary;
             }
         }
     });
 
-    let mut buf = [0u8; 2048];
+    // Room for one byte past the limit, so oversized datagrams show up as such
+    let mut buf = [0u8; MAX_PACKET_LEN + 1];
 
     loop {
         let (len, src) = socket.recv_from(&mut buf).await?;
-        let data = buf[..len].to_vec();
-        if data.is_empty() { continue; }
+        if len > MAX_PACKET_LEN {
+            drops.oversize.fetch_add(1, Ordering::Relaxed);
+            continue;
+        }
+        if last_sweep.elapsed() >= SWEEP_INTERVAL {
+            limits.sweep();
+            last_sweep = Instant::now();
+        }
 
-        // Text commands
-        if data[0].is_ascii_alphabetic() && data[0] != b'I' && data[0] != b'S' {
-            if let Ok(cmd) = String::from_utf8(data) {
-                handle_command(&socket, &rooms, cmd, src).await;
+        // Encrypted transport: answer handshakes and unwrap sealed packets, then
+        // carry on with what was inside
+        let mut opened = None;
+        match protocol::decode(&buf[..len]).map(|p| (p.header.kind, p.payload)) {
+            Ok((MessageType::Handshake, public)) => {
+                if !limits.command(src.ip()) {
+                    drops.commands.fetch_add(1, Ordering::Relaxed);
+                    continue;
+                }
+                socket.accept(public, src).await;
+                continue;
             }
-            continue;
+            Ok((MessageType::Sealed, sealed)) => match socket.open(sealed, src) {
+                Some(packet) => opened = Some(packet),
+                None => {
+                    drops.unopened.fetch_add(1, Ordering::Relaxed);
+                    continue;
+                }
+            },
+            _ => {}
         }
+        let data = opened.as_deref().unwrap_or(&buf[..len]);
+
+        // Everything answered from here (rather than passed to a room) costs
+        // the sender's IP from its command budget, so the server cannot be
+        // used to reflect a flood at someone else
+        let pkt = match protocol::decode(data) {
+            Ok(pkt) => pkt,
+            Err(ProtocolError::VersionMismatch { .. } | ProtocolError::BadMagic) if !limits.command(src.ip()) => {
+                drops.commands.fetch_add(1, Ordering::Relaxed);
+                continue;
+            }
+            Err(ProtocolError::VersionMismatch { theirs, .. }) => {
+                // Reply in our framing; the client's decoder reports the mismatch
+                println!("Rejected {}: protocol v{}", src, theirs);
+                let _ = room::send_command(&socket, "VERSION_MISMATCH", src).await;
+                continue;
+            }
+            Err(ProtocolError::BadMagic) if data.first().is_some_and(u8::is_ascii_alphabetic) => {
+                // Unframed text command from a build that predates the framing
+                let _ = socket.send_raw(b"VERSION_MISMATCH", src).await;
+                continue;
+            }
+            Err(_) => continue,
+        };
 
-        // Binary — forward to the other player in the same room
-        let guard = rooms.lock().await;
-        for (_, room) in guard.iter() {
-            let other = if room.creator == src {
-                room.joiner
-            } else if room.joiner == Some(src) {
-                Some(room.creator)
-            } else {
-                None
+        // CREATE* opens a room; JOIN and REJOIN name theirs; anything else,
+        // game traffic included, belongs to the sender's room. GAME_OVER and
+        // LEAVE end the match, so they go with game traffic and its tag check.
+        // A full inbox drops the datagram, as the network might have.
+        if let Some(cmd) = pkt.command().filter(|&c| c != "GAME_OVER" && c != "LEAVE") {
+            if !limits.command(src.ip()) {
+                drops.commands.fetch_add(1, Ordering::Relaxed);
+                continue;
+            }
+            if cmd.starts_with("CREATE") {
+                if !limits.create(src.ip()) {
+                    drops.creates.fetch_add(1, Ordering::Relaxed);
+                    continue;
+                }
+                room::create(&socket, &registry, &drops, cmd, src).await;
+                continue;
+            }
+            let code = cmd
+                .strip_prefix("JOIN:")
+                .or_else(|| cmd.strip_prefix("REJOIN:"))
+                .map(|args| args.split(':').next().unwrap_or_default().trim());
+            let inbox = {
+                let reg = registry.lock().unwrap();
+                match code {
+                    Some(code) => reg.inbox(code),
+                    None => reg.inbox_of(src),
+                }
             };
-            if let Some(dst) = other {
-                let _ = socket.send_to(&data, dst).await;
-                // If GAME_OVER, also send to the other player
-                if data.starts_with(b"GAME_OVER") {
-                    let _ = socket.send_to(b"GAME_OVER", src).await;
+            match inbox {
+                Some(inbox) if inbox.try_send(RoomMsg::Command { cmd: cmd.to_string(), src }).is_err() => {
+                    drops.inbox_full.fetch_add(1, Ordering::Relaxed);
+                }
+                Some(_) => {}
+                None if code.is_some() => {
+                    let _ = room::send_command(&socket, "NOT_FOUND", src).await;
                 }
-                break;
+                None => {}
+            }
+            continue;
+        }
+        if pkt.header.kind == MessageType::Hello {
+            continue;
+        }
+
+        let inbox = registry.lock().unwrap().inbox_of(src);
+        if let Some(inbox) = inbox {
+            if inbox.try_send(RoomMsg::Packet { data: data.to_vec(), src }).is_err() {
+                drops.inbox_full.fetch_add(1, Ordering::Relaxed);
             }
         }
     }
@@ -0,0 +1,840 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use tokio::time::{Duration, Instant};
+use rand::Rng;
+use puckduel_core::ai::{AiProfile, Difficulty, Personality};
+use puckduel_core::auth::SessionKey;
+use puckduel_core::config::network::{MSG_CHANNEL_CAPACITY, RECONNECT_GRACE_SECS};
+use puckduel_core::protocol::{self, MessageType};
+use puckduel_core::rules::MatchRules;
+use puckduel_core::traversal::{format_candidates, parse_candidates};
+use crate::limit::{Drops, TokenBucket, ROOM_RELAY_BURST, ROOM_RELAY_RATE};
+use crate::sim::{AuthoritativeMatch, Opponent};
+use crate::transport::Transport;
+
+const MAX_ROOMS: usize = 256;
+pub const ROOM_TIMEOUT_SECS: u64 = 120;
+/// How long a Finished room keeps relaying, so the other player's GAME_OVER
+/// and any late packets still get through before it closes.
+const FINISHED_LINGER_SECS: u64 = 5;
+
+/// What the receive loop hands a room's task.
+pub enum RoomMsg {
+    /// A lobby command for the room: `JOIN`, `REJOIN` or `CANDIDATES`.
+    Command { cmd: String, src: SocketAddr },
+    /// Game traffic from a player, GAME_OVER and LEAVE included, as it was
+    /// inside any sealing.
+    Packet { data: Vec<u8>, src: SocketAddr },
+}
+
+/// Each room's inbox by code, plus the room each player address is in, so the
+/// receive loop routes a datagram with one lookup. Locked only for those
+/// lookups and updates, never across a send.
+#[derive(Default)]
+pub struct Registry {
+    inboxes: HashMap<String, mpsc::Sender<RoomMsg>>,
+    by_addr: HashMap<SocketAddr, String>,
+}
+
+pub type SharedRegistry = Arc<Mutex<Registry>>;
+
+impl Registry {
+    /// The inbox of room `code`.
+    pub fn inbox(&self, code: &str) -> Option<mpsc::Sender<RoomMsg>> {
+        self.inboxes.get(code).cloned()
+    }
+
+    /// The inbox of the room `addr` plays in.
+    pub fn inbox_of(&self, addr: SocketAddr) -> Option<mpsc::Sender<RoomMsg>> {
+        self.inbox(self.by_addr.get(&addr)?)
+    }
+
+    /// Indexes `addr` as a player of room `code` in place of `old` (a joiner
+    /// arriving, or a player rejoining from a new address).
+    fn link(&mut self, code: &str, addr: SocketAddr, old: Option<SocketAddr>) {
+        if let Some(old) = old.filter(|&o| o != addr) {
+            if self.by_addr.get(&old).is_some_and(|c| c == code) {
+                self.by_addr.remove(&old);
+            }
+        }
+        self.by_addr.insert(addr, code.to_string());
+    }
+
+    fn remove(&mut self, code: &str) {
+        self.inboxes.remove(code);
+        self.by_addr.retain(|_, c| c != code);
+    }
+}
+
+fn generate_room_code() -> String {
+    format!("{:04}", rand::thread_rng().gen_range(0..10000))
+}
+
+/// `PEER:` message carrying the address the server sees for `addr`, which is
+/// the peer's public (server-reflexive) candidate.
+fn peer_message(addr: SocketAddr) -> String {
+    format!("PEER:{}", SocketAddr::new(addr.ip().to_canonical(), addr.port()))
+}
+
+fn generate_token() -> String {
+    format!("{:016x}", rand::thread_rng().gen::<u64>())
+}
+
+fn generate_key() -> SessionKey {
+    SessionKey::from_bytes(rand::thread_rng().gen())
+}
+
+pub async fn send_command(socket: &Transport, text: &str, dst: SocketAddr) -> std::io::Result<usize> {
+    socket.send_to(&protocol::encode_command(text), dst).await
+}
+
+/// Opens a room for `src` from `CREATE`, `CREATE_AUTH[:<rules>]` or
+/// `CREATE_SOLO[:<difficulty>[:<personality>[:<rules>]]]` and starts its task.
+/// Rules that are missing or out of bounds mean the default ones.
+pub async fn create(socket: &Arc<Transport>, registry: &SharedRegistry, drops: &Arc<Drops>, cmd: &str, src: SocketAddr) {
+    let solo = cmd.strip_prefix("CREATE_SOLO");
+    let reserved = {
+        let mut reg = registry.lock().unwrap();
+        if reg.inboxes.len() >= MAX_ROOMS {
+            Err("BUSY")
+        } else if solo.is_none() && reg.by_addr.contains_key(&src) {
+            Err("ALREADY_HOSTING")
+        } else {
+            let code = loop {
+                let c = match solo {
+                    Some(_) => format!("SOLO_{}", rand::thread_rng().gen_range(1000..9999)),
+                    None => generate_room_code(),
+                };
+                if !reg.inboxes.contains_key(&c) { break c; }
+            };
+            let (tx, rx) = mpsc::channel(MSG_CHANNEL_CAPACITY);
+            reg.inboxes.insert(code.clone(), tx);
+            reg.by_addr.insert(src, code.clone());
+            Ok((code, rx))
+        }
+    };
+    let (code, inbox) = match reserved {
+        Ok(reserved) => reserved,
+        Err(reply) => {
+            let _ = send_command(socket, reply, src).await;
+            return;
+        }
+    };
+
+    let (room, greeting) = match solo {
+        // The server plays the top paddle
+        Some(args) => {
+            let mut args = args.trim().trim_start_matches(':').split(':');
+            let difficulty: Difficulty = args.next().and_then(|a| a.parse().ok()).unwrap_or_default();
+            let personality: Personality = args.next().and_then(|a| a.parse().ok()).unwrap_or_default();
+            let rules: MatchRules = args.next().and_then(|a| a.parse().ok()).unwrap_or_default();
+            let opponent = Opponent::Ai(AiProfile::new(difficulty, personality));
+            let key = generate_key();
+            let sim = AuthoritativeMatch::spawn(socket.clone(), key.clone(), src, opponent, rules);
+            let greeting = vec![format!("KEY:{}", key.to_hex()), "START_AUTH".to_string()];
+            let mut room = Room::new(code.clone(), socket.clone(), registry.clone(), drops.clone(), src, true, Some(sim));
+            room.key = Some(key);
+            room.set_state(RoomState::Playing, "solo game");
+            println!("Solo game {} for {} ({:?}, {:?})", code, src, difficulty, personality);
+            (room, greeting)
+        }
+        None => {
+            let auth_rules = cmd.strip_prefix("CREATE_AUTH");
+            let authoritative = auth_rules.is_some();
+            println!("Room {} created by {}{}", code, src, if authoritative { " (authoritative)" } else { "" });
+            let greeting = vec![format!("CREATED:{}", code)];
+            let mut room = Room::new(code, socket.clone(), registry.clone(), drops.clone(), src, authoritative, None);
+            room.rules = auth_rules.and_then(|a| a.trim().trim_start_matches(':').parse().ok()).unwrap_or_default();
+            (room, greeting)
+        }
+    };
+    tokio::spawn(room.run(inbox, greeting));
+}
+
+/// Where a room is in its life. Every change is logged.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum RoomState {
+    /// Created, waiting for a second player.
+    Waiting,
+    /// Match under way, relayed or simulated.
+    Playing,
+    /// Over (game over, a player left or went idle); closes after `FINISHED_LINGER_SECS`.
+    Finished,
+}
+
+/// One room, owned by its own task: lobby commands and game traffic for it
+/// arrive through its inbox, so a slow or flooded room only holds up itself.
+struct Room {
+    code: String,
+    socket: Arc<Transport>,
+    registry: SharedRegistry,
+    drops: Arc<Drops>,
+    creator: SocketAddr,
+    joiner: Option<SocketAddr>,
+    state: RoomState,
+    /// When the room entered `state`.
+    state_since: Instant,
+    /// Created with `CREATE_AUTH` or `CREATE_SOLO`: the server simulates the match instead of relaying it.
+    authoritative: bool,
+    sim: Option<AuthoritativeMatch>,
+    /// Set when the second player joins and the match starts.
+    started_at: Option<Instant>,
+    /// Reconnect tokens for the creator and joiner, handed out at start.
+    tokens: [String; 2],
+    /// Last packet from the creator and joiner.
+    last_seen: [Instant; 2],
+    /// Each player's own P2P candidates (LAN, IPv6) from `CANDIDATES`, formatted for forwarding.
+    candidates: [String; 2],
+    /// Issued at start; game traffic without a valid tag under it is dropped.
+    key: Option<SessionKey>,
+    /// What the server simulation plays to; relayed rooms leave the rules to the host.
+    rules: MatchRules,
+    /// Game packets dropped for a missing or wrong tag.
+    rejected: u64,
+    /// Bytes this room may still relay; see `ROOM_RELAY_RATE`.
+    relay: TokenBucket,
+    /// Game packets dropped for going over `relay`.
+    throttled: u64,
+}
+
+impl Room {
+    fn new(
+        code: String,
+        socket: Arc<Transport>,
+        registry: SharedRegistry,
+        drops: Arc<Drops>,
+        creator: SocketAddr,
+        authoritative: bool,
+        sim: Option<AuthoritativeMatch>,
+    ) -> Self {
+        let now = Instant::now();
+        Self {
+            code,
+            socket,
+            registry,
+            drops,
+            creator,
+            joiner: None,
+            state: RoomState::Waiting,
+            state_since: now,
+            authoritative,
+            sim,
+            started_at: None,
+            tokens: Default::default(),
+            last_seen: [now; 2],
+            candidates: Default::default(),
+            key: None,
+            rules: MatchRules::default(),
+            rejected: 0,
+            relay: TokenBucket::new(ROOM_RELAY_RATE, ROOM_RELAY_BURST),
+            throttled: 0,
+        }
+    }
+
+    /// 0 for the creator, 1 for the joiner.
+    fn slot_of(&self, addr: SocketAddr) -> Option<usize> {
+        if self.creator == addr {
+            Some(0)
+        } else if self.joiner == Some(addr) {
+            Some(1)
+        } else {
+            None
+        }
+    }
+
+    /// The player opposite `slot`, once there is one.
+    fn other(&self, slot: usize) -> Option<SocketAddr> {
+        if slot == 0 { self.joiner } else { Some(self.creator) }
+    }
+
+    fn set_state(&mut self, state: RoomState, why: &str) {
+        if self.state != state {
+            println!("Room {}: {:?} -> {:?} ({})", self.code, self.state, state, why);
+            self.state = state;
+            self.state_since = Instant::now();
+        }
+    }
+
+    /// Moves the room on by the clock and says whether it should close. Nobody
+    /// joining within `ROOM_TIMEOUT_SECS` or a relayed player silent past the
+    /// reconnect grace ends the room; simulated matches end themselves.
+    fn tick(&mut self) -> bool {
+        let secs = self.state_since.elapsed().as_secs();
+        match self.state {
+            RoomState::Waiting if secs >= ROOM_TIMEOUT_SECS => {
+                self.set_state(RoomState::Finished, "nobody joined");
+                true
+            }
+            RoomState::Waiting => false,
+            RoomState::Playing => {
+                match &self.sim {
+                    Some(m) if m.is_finished() => self.set_state(RoomState::Finished, "match over"),
+                    Some(_) => {}
+                    None if self.last_seen.iter().any(|t| t.elapsed().as_secs() >= RECONNECT_GRACE_SECS) => {
+                        self.set_state(RoomState::Finished, "idle");
+                        return true;
+                    }
+                    None => {}
+                }
+                false
+            }
+            RoomState::Finished => secs >= FINISHED_LINGER_SECS,
+        }
+    }
+
+    async fn send_command(&self, text: &str, dst: SocketAddr) {
+        let _ = send_command(&self.socket, text, dst).await;
+    }
+
+    /// A command the players act on, tagged with the room's key once it has
+    /// one; clients holding the key drop untagged commands.
+    async fn send_signed(&self, text: &str, dst: SocketAddr) {
+        let packet = protocol::encode_command(text);
+        let packet = match &self.key {
+            Some(key) => key.sign(&packet),
+            None => packet,
+        };
+        let _ = self.socket.send_to(&packet, dst).await;
+    }
+
+    /// Sends the creator `greeting`, then serves the inbox until the room is
+    /// done, and finally takes the room out of the registry.
+    async fn run(mut self, mut inbox: mpsc::Receiver<RoomMsg>, greeting: Vec<String>) {
+        for text in &greeting {
+            self.send_signed(text, self.creator).await;
+        }
+        let mut check = tokio::time::interval(Duration::from_secs(1));
+        loop {
+            tokio::select! {
+                msg = inbox.recv() => match msg {
+                    Some(RoomMsg::Command { cmd, src }) => self.command(&cmd, src).await,
+                    Some(RoomMsg::Packet { data, src }) => self.packet(&data, src).await,
+                    None => break,
+                },
+                _ = check.tick() => {
+                    if self.tick() {
+                        break;
+                    }
+                }
+            }
+        }
+
+        let remaining = {
+            let mut reg = self.registry.lock().unwrap();
+            reg.remove(&self.code);
+            reg.inboxes.len()
+        };
+        if self.rejected > 0 {
+            println!("Room {} rejected {} unauthenticated packet(s)", self.code, self.rejected);
+        }
+        if self.throttled > 0 {
+            println!("Room {} dropped {} packet(s) over its relay bandwidth", self.code, self.throttled);
+        }
+        println!("Room {} closed, {} remaining", self.code, remaining);
+    }
+
+    async fn command(&mut self, cmd: &str, src: SocketAddr) {
+        if cmd.starts_with("JOIN:") {
+            self.join(src).await;
+        } else if let Some(args) = cmd.strip_prefix("REJOIN:") {
+            // REJOIN:<code>:<token> — a dropped player comes back, possibly from a new address
+            let token = args.split_once(':').map_or("", |(_, t)| t);
+            self.rejoin(token, src).await;
+        } else if let Some(list) = cmd.strip_prefix("CANDIDATES:") {
+            // CANDIDATES:<addr>,<addr>,... — a player's own addresses, passed on to the other player
+            let Some(slot) = self.slot_of(src).filter(|_| !self.authoritative) else { return };
+            self.candidates[slot] = format_candidates(&parse_candidates(list));
+            if let Some(dst) = self.other(slot) {
+                self.send_signed(&format!("CANDIDATES:{}", self.candidates[slot]), dst).await;
+            }
+        }
+    }
+
+    async fn join(&mut self, src: SocketAddr) {
+        if self.state == RoomState::Finished {
+            self.send_command("NOT_FOUND", src).await;
+            return;
+        }
+        if self.joiner.is_some() {
+            self.send_command("FULL", src).await;
+            return;
+        }
+        if self.creator == src {
+            self.send_command("CANNOT_JOIN_OWN", src).await;
+            return;
+        }
+        self.joiner = Some(src);
+        self.registry.lock().unwrap().link(&self.code, src, None);
+        self.set_state(RoomState::Playing, &format!("{} joined", src));
+        self.started_at = Some(Instant::now());
+        self.last_seen = [Instant::now(); 2];
+        self.tokens = [generate_token(), generate_token()];
+        let key = generate_key();
+        self.key = Some(key.clone());
+        let (code, host) = (&self.code, self.creator);
+        let host_token = format!("TOKEN:{}:{}", code, self.tokens[0]);
+        let join_token = format!("TOKEN:{}:{}", code, self.tokens[1]);
+        let key_message = format!("KEY:{}", key.to_hex());
+
+        // The key goes first, so the players can check everything after it
+        self.send_command(&key_message, host).await;
+        self.send_command(&key_message, src).await;
+
+        if self.authoritative {
+            self.sim = Some(AuthoritativeMatch::spawn(self.socket.clone(), key, host, Opponent::Player(src), self.rules));
+            self.send_signed("JOINED", src).await;
+            self.send_signed(&host_token, host).await;
+            self.send_signed(&join_token, src).await;
+            self.send_signed("START_AUTH", host).await;
+            self.send_signed("START_AUTH", src).await;
+            println!("Authoritative game started in room {}: {} vs {}", self.code, host, src);
+            return;
+        }
+
+        // Tell each player the other's public address and any local candidates
+        // the host registered (for P2P hole-punching), and the salt their
+        // direct channel is keyed with
+        let salt = format!("SALT:{}", generate_token());
+        self.send_signed(&salt, host).await;
+        self.send_signed(&salt, src).await;
+        self.send_signed(&peer_message(src), host).await;
+        self.send_signed(&peer_message(host), src).await;
+        if !self.candidates[0].is_empty() {
+            self.send_signed(&format!("CANDIDATES:{}", self.candidates[0]), src).await;
+        }
+
+        self.send_signed("JOINED", src).await;
+        self.send_signed(&host_token, host).await;
+        self.send_signed(&join_token, src).await;
+        self.send_signed("START", host).await;
+        self.send_signed("START", src).await;
+        println!("Game started in room {}: {} vs {} (P2P capable)", self.code, host, src);
+    }
+
+    async fn rejoin(&mut self, token: &str, src: SocketAddr) {
+        let slot = self.tokens.iter().position(|t| t == token).filter(|_| self.state == RoomState::Playing);
+        let (Some(started_at), Some(slot)) = (self.started_at, slot) else {
+            self.send_command("REJOIN_DENIED", src).await;
+            return;
+        };
+        let old = if slot == 0 {
+            Some(std::mem::replace(&mut self.creator, src))
+        } else {
+            self.joiner.replace(src)
+        };
+        self.registry.lock().unwrap().link(&self.code, src, old);
+        self.last_seen[slot] = Instant::now();
+        if let Some(sim) = &self.sim {
+            sim.rejoin(slot, src);
+        }
+        // Slot, time since START (to line up frame numbers), how the match runs,
+        // the session key and, for a direct channel, a new salt to key it with
+        let mode = if self.authoritative { "START_AUTH" } else { "START" };
+        let key = self.key.as_ref().map(SessionKey::to_hex).unwrap_or_default();
+        let salt = if self.authoritative { String::new() } else { generate_token() };
+        let reply = format!("REJOINED:{}:{}:{}:{}:{}", slot, started_at.elapsed().as_millis(), mode, key, salt);
+        self.send_signed(&reply, src).await;
+        // The other player rekeys and can hole-punch to the new address
+        if !self.authoritative {
+            if let Some(dst) = self.other(slot) {
+                self.send_signed(&format!("SALT:{}", salt), dst).await;
+                self.send_signed(&peer_message(src), dst).await;
+            }
+        }
+        println!("Player {} rejoined room {} as {}", src, self.code, if slot == 0 { "host" } else { "joiner" });
+    }
+
+    /// Game traffic: forwarded to the other player, or fed to the server
+    /// simulation in authoritative rooms. Once the room has started only
+    /// packets tagged with its key count; a spoofed source address alone gets
+    /// nothing relayed or simulated, and cannot end the room either.
+    async fn packet(&mut self, data: &[u8], src: SocketAddr) {
+        let Ok(pkt) = protocol::decode(data) else { return };
+        let Some(slot) = self.slot_of(src) else { return };
+        if self.key.as_ref().is_some_and(|k| k.verify(data).is_none()) {
+            self.rejected += 1;
+            if self.rejected == 1 {
+                println!("Room {}: rejecting unauthenticated {:?} from {}", self.code, pkt.header.kind, src);
+            }
+            return;
+        }
+        self.last_seen[slot] = Instant::now();
+        if pkt.command() == Some("LEAVE") {
+            self.leave(slot, src).await;
+            return;
+        }
+        match &self.sim {
+            Some(sim) => {
+                sim.heard(slot);
+                match pkt.header.kind {
+                    MessageType::Input => {
+                        if let Some(ptr) = protocol::decode_input(pkt.payload) {
+                            sim.set_input(slot, pkt.header.seq, ptr);
+                        }
+                    }
+                    // The server is the other end here, so it answers pings itself
+                    MessageType::Ping => {
+                        if let Some(key) = &self.key {
+                            let pong = key.sign(&protocol::encode(MessageType::Pong, pkt.header.seq, &[]));
+                            let _ = self.socket.send_to(&pong, src).await;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            None => {
+                if !self.relay.take(data.len() as f64) {
+                    self.throttled += 1;
+                    self.drops.relay.fetch_add(1, Ordering::Relaxed);
+                    return;
+                }
+                if let Some(dst) = self.other(slot) {
+                    let _ = self.socket.send_to(data, dst).await;
+                }
+                if pkt.command() == Some("GAME_OVER") {
+                    self.set_state(RoomState::Finished, "game over");
+                }
+            }
+        }
+    }
+
+    /// `LEAVE`: the player in `slot` quit. A simulated match forfeits them and
+    /// ends itself; in a relayed one the other player is told, and wins.
+    async fn leave(&mut self, slot: usize, src: SocketAddr) {
+        if self.state == RoomState::Playing {
+            if let Some(sim) = &self.sim {
+                sim.leave(slot);
+                return;
+            }
+            if let Some(dst) = self.other(slot) {
+                self.send_signed("OPPONENT_LEFT", dst).await;
+            }
+        }
+        self.set_state(RoomState::Finished, &format!("{} left", src));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use puckduel_core::game::RenderState;
+    use puckduel_core::rules::Overtime;
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    /// A registry with room `code` hosted by `host`, as `create` leaves it.
+    fn registry_with(code: &str, host: SocketAddr) -> (Registry, mpsc::Receiver<RoomMsg>) {
+        let mut reg = Registry::default();
+        let (tx, rx) = mpsc::channel(MSG_CHANNEL_CAPACITY);
+        reg.inboxes.insert(code.to_string(), tx);
+        reg.by_addr.insert(host, code.to_string());
+        (reg, rx)
+    }
+
+    #[test]
+    fn test_registry_routes_both_players_to_their_room() {
+        let (host, guest, stranger) = (addr(5001), addr(5002), addr(5003));
+        let (mut reg, _rx) = registry_with("1234", host);
+        reg.link("1234", guest, None);
+
+        let room = reg.inbox("1234").unwrap();
+        assert!(reg.inbox_of(host).unwrap().same_channel(&room));
+        assert!(reg.inbox_of(guest).unwrap().same_channel(&room));
+        assert!(reg.inbox_of(stranger).is_none());
+        assert!(reg.inbox("0000").is_none());
+    }
+
+    #[test]
+    fn test_registry_rejoin_moves_the_address() {
+        let (host, guest, moved) = (addr(5001), addr(5002), addr(5004));
+        let (mut reg, _rx) = registry_with("1234", host);
+        reg.link("1234", guest, None);
+        reg.link("1234", moved, Some(guest));
+
+        assert!(reg.inbox_of(guest).is_none());
+        assert!(reg.inbox_of(moved).is_some());
+        assert!(reg.inbox_of(host).is_some());
+
+        // Rejoining from the same address keeps it
+        reg.link("1234", host, Some(host));
+        assert!(reg.inbox_of(host).is_some());
+    }
+
+    #[test]
+    fn test_registry_rejoin_leaves_an_old_address_now_in_another_room() {
+        let (host, guest, other_host) = (addr(5001), addr(5002), addr(5005));
+        let (mut reg, _rx) = registry_with("1234", host);
+        let (tx, _other_rx) = mpsc::channel(MSG_CHANNEL_CAPACITY);
+        reg.inboxes.insert("5678".to_string(), tx);
+        reg.link("5678", other_host, None);
+
+        // The guest's old address has since been taken by the host of room 5678
+        reg.link("1234", guest, Some(other_host));
+        assert!(reg.inbox_of(other_host).unwrap().same_channel(&reg.inbox("5678").unwrap()));
+    }
+
+    #[test]
+    fn test_registry_remove_clears_every_index_entry() {
+        let (host, guest, moved, other_host) = (addr(5001), addr(5002), addr(5004), addr(5005));
+        let (mut reg, _rx) = registry_with("1234", host);
+        reg.link("1234", guest, None);
+        reg.link("1234", moved, Some(guest));
+        let (tx, _other_rx) = mpsc::channel(MSG_CHANNEL_CAPACITY);
+        reg.inboxes.insert("5678".to_string(), tx);
+        reg.link("5678", other_host, None);
+
+        reg.remove("1234");
+        assert!(reg.inbox("1234").is_none());
+        assert!(!reg.by_addr.values().any(|c| c == "1234"));
+        assert!(reg.inbox_of(other_host).is_some());
+        assert_eq!(reg.by_addr.len(), 1);
+    }
+
+    /// Room `1234` hosted by `host`, running on its own task: relayed, or a
+    /// solo game against the server's AI.
+    struct TestRoom {
+        registry: SharedRegistry,
+        inbox:    mpsc::Sender<RoomMsg>,
+        host:     tokio::net::UdpSocket,
+        guest:    tokio::net::UdpSocket,
+    }
+
+    impl TestRoom {
+        async fn open() -> Self {
+            Self::spawn(None).await
+        }
+
+        /// A solo game, already playing, and its session key.
+        async fn open_solo() -> (Self, SessionKey) {
+            let key = generate_key();
+            (Self::spawn(Some(key.clone())).await, key)
+        }
+
+        async fn spawn(solo: Option<SessionKey>) -> Self {
+            let socket = Arc::new(Transport::bind("127.0.0.1:0").await.unwrap());
+            let host = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            let guest = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            let host_addr = host.local_addr().unwrap();
+            let (reg, rx) = registry_with("1234", host_addr);
+            let registry = Arc::new(Mutex::new(reg));
+            let inbox = registry.lock().unwrap().inbox("1234").unwrap();
+            let sim = solo.as_ref().map(|key| {
+                AuthoritativeMatch::spawn(socket.clone(), key.clone(), host_addr, Opponent::Ai(AiProfile::default()), MatchRules::default())
+            });
+            let mut room = Room::new(
+                "1234".to_string(),
+                socket,
+                registry.clone(),
+                Arc::new(Drops::default()),
+                host_addr,
+                sim.is_some(),
+                sim,
+            );
+            if solo.is_some() {
+                room.key = solo;
+                room.set_state(RoomState::Playing, "solo game");
+            }
+            tokio::spawn(room.run(rx, Vec::new()));
+            Self { registry, inbox, host, guest }
+        }
+
+        fn is_open(&self) -> bool {
+            self.registry.lock().unwrap().inbox("1234").is_some()
+        }
+
+        /// Joins the guest and returns the session key the room handed out.
+        async fn start(&self) -> SessionKey {
+            self.command("JOIN:1234", &self.guest).await;
+            loop {
+                if let Some(hex) = recv_command(&self.guest).await.strip_prefix("KEY:") {
+                    return SessionKey::from_hex(hex).unwrap();
+                }
+            }
+        }
+
+        async fn command(&self, cmd: &str, from: &tokio::net::UdpSocket) {
+            let msg = RoomMsg::Command { cmd: cmd.to_string(), src: from.local_addr().unwrap() };
+            self.inbox.send(msg).await.unwrap();
+        }
+
+        async fn packet(&self, data: Vec<u8>, from: &tokio::net::UdpSocket) {
+            self.inbox.send(RoomMsg::Packet { data, src: from.local_addr().unwrap() }).await.unwrap();
+        }
+    }
+
+    /// The next datagram to `sock`, as it arrived.
+    async fn recv(sock: &tokio::net::UdpSocket) -> Vec<u8> {
+        let mut buf = [0u8; 1500];
+        let (n, _) = tokio::time::timeout(Duration::from_secs(1), sock.recv_from(&mut buf)).await.unwrap().unwrap();
+        buf[..n].to_vec()
+    }
+
+    async fn recv_command(sock: &tokio::net::UdpSocket) -> String {
+        let data = recv(sock).await;
+        protocol::decode(&data).unwrap().command().unwrap().to_string()
+    }
+
+    /// Lets `secs` pass on a paused clock. Sockets are only read with it
+    /// running, as a paused clock jumps ahead while a task waits on I/O.
+    async fn wait(secs: f64) {
+        tokio::time::pause();
+        tokio::time::sleep(Duration::from_secs_f64(secs)).await;
+        tokio::time::resume();
+    }
+
+    #[tokio::test]
+    async fn test_room_nobody_joins_closes_after_timeout() {
+        let room = TestRoom::open().await;
+        wait(ROOM_TIMEOUT_SECS as f64 - 0.5).await;
+        assert!(room.is_open());
+        wait(1.0).await;
+        assert!(!room.is_open());
+    }
+
+    #[tokio::test]
+    async fn test_room_signs_commands_after_the_key() {
+        let room = TestRoom::open().await;
+        let key = room.start().await;
+        let mut seen = Vec::new();
+        while !seen.contains(&"START".to_string()) {
+            let data = recv(&room.guest).await;
+            assert!(key.verify(&data).is_some());
+            seen.push(protocol::decode(&data).unwrap().command().unwrap().to_string());
+        }
+        assert!(seen.iter().any(|c| c.starts_with("PEER:")));
+
+        // Candidates the host sends mid-match reach the guest signed too
+        room.command("CANDIDATES:10.0.0.2:4000", &room.host).await;
+        let data = recv(&room.guest).await;
+        assert!(key.verify(&data).is_some());
+        assert_eq!(protocol::decode(&data).unwrap().command(), Some("CANDIDATES:10.0.0.2:4000"));
+    }
+
+    #[tokio::test]
+    async fn test_room_rejoin_salts_the_direct_channel_afresh() {
+        let room = TestRoom::open().await;
+        room.start().await;
+        let (mut salt, mut token) = (None, None);
+        while token.is_none() {
+            let cmd = recv_command(&room.host).await;
+            salt = salt.or(cmd.strip_prefix("SALT:").map(str::to_string));
+            token = cmd.strip_prefix("TOKEN:1234:").map(str::to_string);
+        }
+
+        let rejoined = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        room.command(&format!("REJOIN:1234:{}", token.unwrap()), &rejoined).await;
+        let reply = recv_command(&rejoined).await;
+        let new_salt = reply.rsplit(':').next().unwrap().to_string();
+        assert!(reply.starts_with("REJOINED:0:"));
+        assert_ne!(Some(&new_salt), salt.as_ref());
+
+        // The guest gets the same salt to rekey its end with
+        let mut salts = Vec::new();
+        while salts.len() < 2 {
+            if let Some(s) = recv_command(&room.guest).await.strip_prefix("SALT:") {
+                salts.push(s.to_string());
+            }
+        }
+        assert_eq!(Some(&salts[0]), salt.as_ref());
+        assert_eq!(salts[1], new_salt);
+    }
+
+    #[tokio::test]
+    async fn test_room_leave_tells_the_other_player_and_lingers() {
+        let room = TestRoom::open().await;
+        let key = room.start().await;
+
+        room.packet(key.sign(&protocol::encode_command("LEAVE")), &room.host).await;
+        loop {
+            let data = recv(&room.guest).await;
+            if protocol::decode(&data).unwrap().command() == Some("OPPONENT_LEFT") {
+                // A forged one would hand the match to its receiver
+                assert!(key.verify(&data).is_some());
+                break;
+            }
+        }
+        wait(FINISHED_LINGER_SECS as f64 - 0.5).await;
+        assert!(room.is_open());
+        // Finished rooms turn away new players
+        room.command("JOIN:1234", &room.guest).await;
+        assert_eq!(recv_command(&room.guest).await, "NOT_FOUND");
+        wait(1.0).await;
+        assert!(!room.is_open());
+    }
+
+    #[tokio::test]
+    async fn test_room_game_over_is_relayed_then_closes() {
+        let room = TestRoom::open().await;
+        let key = room.start().await;
+        while !recv_command(&room.host).await.starts_with("START") {}
+
+        let game_over = key.sign(&protocol::encode_command("GAME_OVER"));
+        room.packet(game_over.clone(), &room.guest).await;
+        assert_eq!(recv(&room.host).await, game_over);
+        wait(FINISHED_LINGER_SECS as f64 - 0.5).await;
+        assert!(room.is_open());
+        wait(1.0).await;
+        assert!(!room.is_open());
+    }
+
+    #[tokio::test]
+    async fn test_room_ignores_untagged_leave_and_ends_when_idle() {
+        let room = TestRoom::open().await;
+        room.start().await;
+
+        room.packet(protocol::encode_command("LEAVE"), &room.host).await;
+        room.packet(protocol::encode_command("GAME_OVER"), &room.host).await;
+        wait(RECONNECT_GRACE_SECS as f64 - 0.5).await;
+        assert!(room.is_open());
+        // Idle rooms close at once, with no linger
+        wait(1.0).await;
+        assert!(!room.is_open());
+    }
+
+    #[tokio::test]
+    async fn test_solo_room_closes_when_its_player_goes_silent() {
+        let (room, _key) = TestRoom::open_solo().await;
+        wait(RECONNECT_GRACE_SECS as f64 - 0.5).await;
+        assert!(room.is_open());
+        // The match gives up on the player, then the room lingers
+        wait(2.0).await;
+        assert!(room.is_open());
+        wait(FINISHED_LINGER_SECS as f64).await;
+        assert!(!room.is_open());
+    }
+
+    #[tokio::test]
+    async fn test_solo_room_ends_on_leave() {
+        let (room, key) = TestRoom::open_solo().await;
+        room.packet(key.sign(&protocol::encode_command("LEAVE")), &room.host).await;
+        wait(2.0).await;
+        assert!(room.is_open());
+        wait(FINISHED_LINGER_SECS as f64).await;
+        assert!(!room.is_open());
+    }
+
+    #[tokio::test]
+    async fn test_solo_game_plays_to_the_rules_it_was_created_with() {
+        let socket = Arc::new(Transport::bind("127.0.0.1:0").await.unwrap());
+        let registry: SharedRegistry = Default::default();
+        let player = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let cmd = "CREATE_SOLO:Easy:Defender:3,1,120,Draw,3";
+        create(&socket, &registry, &Arc::new(Drops::default()), cmd, player.local_addr().unwrap()).await;
+
+        let rules = MatchRules { target_score: 3, win_by_two: true, time_limit: Some(120.0), overtime: Overtime::Draw, best_of: 3 };
+        loop {
+            let data = recv(&player).await;
+            let pkt = protocol::decode(&data).unwrap();
+            if pkt.header.kind == MessageType::State {
+                let state: RenderState = bincode::deserialize(pkt.payload).unwrap();
+                assert_eq!(state.rules, rules);
+                break;
+            }
+        }
+    }
+}
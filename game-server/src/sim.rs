@@ -0,0 +1,170 @@
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::task::JoinHandle;
+use tokio::time::{Duration, Instant};
+use puckduel_core::ai::{AiController, AiProfile, Controller};
+use puckduel_core::auth::SessionKey;
+use puckduel_core::config::network::{HEARTBEAT_TIMEOUT_MS, RECONNECT_GRACE_SECS, TARGET_FPS};
+use puckduel_core::game::GameState;
+use puckduel_core::protocol::{self, Arrival, MessageType, SequenceTracker};
+use puckduel_core::rules::MatchRules;
+use crate::transport::Transport;
+
+/// Who controls the client (top) paddle.
+pub enum Opponent {
+    Player(SocketAddr),
+    Ai(AiProfile),
+}
+
+/// Pointers for both paddles plus where each player is and when they were
+/// last heard from.
+struct Inputs {
+    ptr:       [[f32; 2]; 2],
+    streams:   [SequenceTracker; 2],
+    last_seen: [Instant; 2],
+    /// Players who sent `LEAVE`; they count as gone at once.
+    left:      [bool; 2],
+    /// Human players by slot; only the host in a solo game.
+    players:   Vec<SocketAddr>,
+}
+
+/// A match simulated on the server. Players only send `Input` packets; the
+/// server runs `GameState::server_update` and broadcasts every `State`, tagged
+/// with the room's session key.
+pub struct AuthoritativeMatch {
+    inputs: Arc<Mutex<Inputs>>,
+    task:   JoinHandle<()>,
+}
+
+impl AuthoritativeMatch {
+    /// Starts the 60 Hz simulation with `host` on the bottom paddle, played
+    /// under `rules`.
+    pub fn spawn(socket: Arc<Transport>, key: SessionKey, host: SocketAddr, opponent: Opponent, rules: MatchRules) -> Self {
+        let mut gs = GameState::new();
+        gs.set_rules(rules);
+        let (players, ai) = match opponent {
+            Opponent::Player(addr) => (vec![host, addr], None),
+            Opponent::Ai(profile) => (vec![host], Some(AiController::with_profile(1, &gs, profile))),
+        };
+        let inputs = Arc::new(Mutex::new(Inputs {
+            ptr:       [[gs.host_paddle.x, gs.host_paddle.y], [gs.client_paddle.x, gs.client_paddle.y]],
+            streams:   [SequenceTracker::new(); 2],
+            last_seen: [Instant::now(); 2],
+            left:      [false; 2],
+            players,
+        }));
+        let task = tokio::spawn(run(socket, key, gs, ai, inputs.clone()));
+        Self { inputs, task }
+    }
+
+    /// Pointer `seq` for `slot` (0 = host, 1 = joiner); applied only if it is
+    /// newer than the last one received.
+    pub fn set_input(&self, slot: usize, seq: u32, ptr: [f32; 2]) {
+        let mut inputs = self.inputs.lock().unwrap();
+        if inputs.streams[slot].record(seq) == Arrival::Newest {
+            inputs.ptr[slot] = ptr;
+        }
+    }
+
+    /// Any packet from `slot` counts as a heartbeat.
+    pub fn heard(&self, slot: usize) {
+        self.inputs.lock().unwrap().last_seen[slot] = Instant::now();
+    }
+
+    /// Points `slot` at the address it rejoined from. Its packet counter
+    /// restarted with the new connection, so sequence tracking does too.
+    pub fn rejoin(&self, slot: usize, addr: SocketAddr) {
+        let mut inputs = self.inputs.lock().unwrap();
+        if let Some(player) = inputs.players.get_mut(slot) {
+            *player = addr;
+            inputs.streams[slot] = SequenceTracker::new();
+            inputs.last_seen[slot] = Instant::now();
+        }
+    }
+
+    /// `slot` quit: forfeited without waiting out the reconnect grace.
+    pub fn leave(&self, slot: usize) {
+        self.inputs.lock().unwrap().left[slot] = true;
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+}
+
+impl Drop for AuthoritativeMatch {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn run(
+    socket: Arc<Transport>,
+    key: SessionKey,
+    mut gs: GameState,
+    mut ai: Option<AiController>,
+    inputs: Arc<Mutex<Inputs>>,
+) {
+    let dt = 1.0 / TARGET_FPS as f32;
+    let mut interval = tokio::time::interval(Duration::from_secs_f32(dt));
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    let mut seq: u32 = 0;
+
+    loop {
+        interval.tick().await;
+
+        let (players, ptrs, away) = {
+            let mut inputs = inputs.lock().unwrap();
+            let humans = inputs.players.len();
+            let silent = |slot: usize, limit: Duration| inputs.last_seen[slot].elapsed() > limit;
+            let grace = Duration::from_secs(RECONNECT_GRACE_SECS);
+            let gone: Vec<usize> = (0..humans).filter(|&s| inputs.left[s] || silent(s, grace)).collect();
+            let away = (0..humans).any(|s| silent(s, Duration::from_millis(HEARTBEAT_TIMEOUT_MS)));
+            match gone[..] {
+                [] => {}
+                // The player who stayed wins once the other's grace period is over
+                [slot] if humans == 2 => {
+                    let how = if inputs.left[slot] { "quit" } else { "did not return" };
+                    println!("Player {} left {:?} and {}: forfeit", inputs.players[slot], inputs.players, how);
+                    gs.forfeit(1 - slot);
+                }
+                _ => {
+                    println!("Authoritative match for {:?} abandoned: everyone quit or sent no input for {}s", inputs.players, RECONNECT_GRACE_SECS);
+                    return;
+                }
+            }
+            if let Some(ai) = ai.as_mut() {
+                inputs.ptr[1] = ai.target(&gs, dt);
+            }
+            (inputs.players.clone(), inputs.ptr, away)
+        };
+        // Hold the match still while someone is dropped out so they can rejoin
+        // where they left off
+        if !away {
+            gs.server_update(dt, ptrs[0], ptrs[1]);
+        }
+
+        seq = seq.wrapping_add(1);
+        if let Ok(encoded) = bincode::serialize(&gs.to_render()) {
+            let out = key.sign(&protocol::encode(MessageType::State, seq, &encoded));
+            for &dst in &players {
+                let _ = socket.send_to(&out, dst).await;
+            }
+        }
+
+        if gs.game_over {
+            let out = key.sign(&protocol::encode_command("GAME_OVER"));
+            for &dst in &players {
+                let _ = socket.send_to(&out, dst).await;
+            }
+            let inputs = inputs.lock().unwrap();
+            let loss: Vec<String> = players
+                .iter()
+                .zip(&inputs.streams)
+                .map(|(p, s)| format!("{} {:.1}%", p, s.loss_ratio() * 100.0))
+                .collect();
+            println!("Authoritative match for {:?} finished: {:?} (input loss: {})", players, gs.score, loss.join(", "));
+            return;
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+use rand::Rng;
+use puckduel_core::crypto::{Cipher, Handshake};
+
+/// An encrypted channel to one client.
+struct Channel {
+    cipher:        Arc<Cipher>,
+    /// The client's handshake key; a repeated handshake with it keeps the channel.
+    client_public: Vec<u8>,
+    reply:         Vec<u8>,
+    last_used:     Instant,
+}
+
+/// A known handshake repeated from a new address, waiting for proof.
+struct Move {
+    client_public: Vec<u8>,
+    since:         Instant,
+}
+
+/// The server's UDP socket plus an encrypted channel for every client that
+/// sent a `Handshake`. Clients that did not are spoken to in plaintext.
+pub struct Transport {
+    socket:   UdpSocket,
+    channels: Mutex<HashMap<SocketAddr, Channel>>,
+    /// New address to the channel it claims; locked after `channels`.
+    moves:    Mutex<HashMap<SocketAddr, Move>>,
+}
+
+impl Transport {
+    pub async fn bind(addr: &str) -> std::io::Result<Self> {
+        Ok(Self {
+            socket:   UdpSocket::bind(addr).await?,
+            channels: Mutex::new(HashMap::new()),
+            moves:    Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub async fn recv_from(&self, buf: &mut [u8]) -> std::io::Result<(usize, SocketAddr)> {
+        self.socket.recv_from(buf).await
+    }
+
+    /// Answers a handshake from `src`. A client repeating one gets the same
+    /// reply. If it comes from a new address (e.g. after switching networks),
+    /// the channel stays where it is until a packet sealed under it arrives
+    /// from there: the handshake is plaintext, so anyone could replay it.
+    pub async fn accept(&self, client_public: &[u8], src: SocketAddr) {
+        let reply = {
+            let mut channels = self.channels.lock().unwrap();
+            let existing = channels.iter_mut().find(|(_, c)| c.client_public == client_public);
+            if let Some((&addr, channel)) = existing {
+                if addr == src {
+                    channel.last_used = Instant::now();
+                } else {
+                    self.moves
+                        .lock()
+                        .unwrap()
+                        .insert(src, Move { client_public: client_public.to_vec(), since: Instant::now() });
+                }
+                channel.reply.clone()
+            } else {
+                let handshake = Handshake::new(rand::thread_rng().gen());
+                let Some(cipher) = handshake.finish(client_public, false) else { return };
+                let reply = handshake.packet();
+                channels.insert(
+                    src,
+                    Channel {
+                        cipher:        Arc::new(cipher),
+                        client_public: client_public.to_vec(),
+                        reply:         reply.clone(),
+                        last_used:     Instant::now(),
+                    },
+                );
+                reply
+            }
+        };
+        let _ = self.socket.send_to(&reply, src).await;
+    }
+
+    /// The packet inside a `Sealed` payload from `src`. From an address that
+    /// repeated a known handshake, one that opens moves that channel here.
+    pub fn open(&self, payload: &[u8], src: SocketAddr) -> Option<Vec<u8>> {
+        let mut channels = self.channels.lock().unwrap();
+        if let Some(channel) = channels.get_mut(&src) {
+            let packet = channel.cipher.open(payload)?;
+            channel.last_used = Instant::now();
+            return Some(packet);
+        }
+        let mut moves = self.moves.lock().unwrap();
+        let client_public = &moves.get(&src)?.client_public;
+        let from = channels.iter().find(|(_, c)| &c.client_public == client_public).map(|(&a, _)| a)?;
+        let packet = channels[&from].cipher.open(payload)?;
+        moves.remove(&src);
+        let channel = channels.remove(&from)?;
+        channels.insert(src, Channel { last_used: Instant::now(), ..channel });
+        Some(packet)
+    }
+
+    /// Sends `data` to `dst`, sealed if `dst` has a channel.
+    pub async fn send_to(&self, data: &[u8], dst: SocketAddr) -> std::io::Result<usize> {
+        let cipher = self.channels.lock().unwrap().get(&dst).map(|c| c.cipher.clone());
+        match cipher {
+            Some(cipher) => self.socket.send_to(&cipher.seal(data), dst).await,
+            None => self.socket.send_to(data, dst).await,
+        }
+    }
+
+    /// Sends `data` as is, e.g. to a client whose framing we cannot speak.
+    pub async fn send_raw(&self, data: &[u8], dst: SocketAddr) -> std::io::Result<usize> {
+        self.socket.send_to(data, dst).await
+    }
+
+    /// Forgets channels nothing has come through for `idle`, and moves that
+    /// went unproven as long.
+    pub fn expire(&self, idle: Duration) {
+        self.channels.lock().unwrap().retain(|_, c| c.last_used.elapsed() < idle);
+        self.moves.lock().unwrap().retain(|_, m| m.since.elapsed() < idle);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use puckduel_core::protocol::{self, MessageType};
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    /// A client's channel to `transport`, set up from `src`.
+    async fn connect(transport: &Transport, src: SocketAddr) -> (Handshake, Cipher) {
+        let client = Handshake::new([7; 32]);
+        transport.accept(&client.public(), src).await;
+        let server_public = transport.channels.lock().unwrap()[&src].reply.clone();
+        let cipher = client.finish(protocol::decode(&server_public).unwrap().payload, true).unwrap();
+        (client, cipher)
+    }
+
+    fn sealed(cipher: &Cipher, command: &str) -> Vec<u8> {
+        let packet = cipher.seal(&protocol::encode_command(command));
+        let outer = protocol::decode(&packet).unwrap();
+        assert_eq!(outer.header.kind, MessageType::Sealed);
+        outer.payload.to_vec()
+    }
+
+    #[tokio::test]
+    async fn test_repeated_handshake_moves_channel_only_once_proven() {
+        let transport = Transport::bind("127.0.0.1:0").await.unwrap();
+        let (home, roaming, attacker) = (addr(40001), addr(40002), addr(40003));
+        let (client, cipher) = connect(&transport, home).await;
+        let rejoin = sealed(&cipher, "REJOIN:token");
+        assert!(transport.open(&rejoin, home).is_some());
+
+        // A replayed handshake and a replayed packet do not take the channel
+        transport.accept(&client.public(), attacker).await;
+        assert_eq!(transport.open(&rejoin, attacker), None);
+        assert!(transport.channels.lock().unwrap().contains_key(&home));
+
+        // The client itself, from a new address, moves it with a fresh packet
+        transport.accept(&client.public(), roaming).await;
+        assert!(transport.open(&sealed(&cipher, "REJOIN:token"), roaming).is_some());
+        let channels = transport.channels.lock().unwrap();
+        assert!(channels.contains_key(&roaming));
+        assert!(!channels.contains_key(&home));
+    }
+}
@@ -0,0 +1,328 @@
+use std::str::FromStr;
+use serde::{Serialize, Deserialize};
+use crate::config::ai::*;
+use crate::game::GameState;
+use crate::physics::{predict_trajectory, crossing_at_y};
+
+/// Something that decides where a paddle should be each tick: the built-in AI,
+/// a scripted test driver, or a bot on the game server.
+pub trait Controller {
+    /// Pointer position for this tick, in table coordinates.
+    fn target(&mut self, view: &GameState, dt: f32) -> [f32; 2];
+}
+
+/// How sharp the AI plays.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq, Default)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+    Expert,
+}
+
+/// How the AI chooses between attacking and defending.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq, Default)]
+pub enum Personality {
+    #[default]
+    Balanced,
+    /// Plays high up the table and attacks anything in its half.
+    Attacker,
+    /// Stays on the goal line and only clears pucks that come close.
+    Defender,
+    /// Strikes the puck off-centre so shots bank off the side walls.
+    BankShot,
+}
+
+/// Parses the variant name, as sent in a `CREATE_SOLO:<difficulty>` command.
+impl FromStr for Difficulty {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Easy" => Ok(Self::Easy),
+            "Normal" => Ok(Self::Normal),
+            "Hard" => Ok(Self::Hard),
+            "Expert" => Ok(Self::Expert),
+            _ => Err(format!("unknown difficulty: {}", s)),
+        }
+    }
+}
+
+impl FromStr for Personality {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Balanced" => Ok(Self::Balanced),
+            "Attacker" => Ok(Self::Attacker),
+            "Defender" => Ok(Self::Defender),
+            "BankShot" => Ok(Self::BankShot),
+            _ => Err(format!("unknown personality: {}", s)),
+        }
+    }
+}
+
+/// Tuning for [`AiController`]. `Default` is Normal/Balanced, i.e. the `config::ai` values.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AiProfile {
+    /// Seconds between re-plans; the AI's reaction delay.
+    pub think_interval:  f32,
+    pub reaction_lerp:   f32,
+    pub aim_error:       [f32; 2],
+    pub chase_speed:     f32,
+    pub intercept_speed: f32,
+    pub return_speed:    f32,
+    pub home_y:          f32,
+    pub defensive_y:     f32,
+    /// Loose pucks closer than this (from the AI's own goal) are attacked.
+    pub attack_reach:    f32,
+    /// Sideways offset of the strike point, for angled bank shots.
+    pub bank_offset:     f32,
+}
+
+impl Default for AiProfile {
+    fn default() -> Self {
+        Self::new(Difficulty::default(), Personality::default())
+    }
+}
+
+impl AiProfile {
+    pub fn new(difficulty: Difficulty, personality: Personality) -> Self {
+        // (reaction delay, aim error, paddle speed) multipliers
+        let (delay, error, speed) = match difficulty {
+            Difficulty::Easy => (2.0, 2.2, 0.6),
+            Difficulty::Normal => (1.0, 1.0, 1.0),
+            Difficulty::Hard => (0.7, 0.5, 1.3),
+            Difficulty::Expert => (0.5, 0.2, 1.6),
+        };
+        let mut profile = Self {
+            think_interval:  THINK_INTERVAL * delay,
+            reaction_lerp:   (REACTION_LERP / delay).min(1.0),
+            aim_error:       [AIM_ERROR_X * error, AIM_ERROR_Y * error],
+            chase_speed:     CHASE_SPEED * speed,
+            intercept_speed: INTERCEPT_SPEED * speed,
+            return_speed:    RETURN_SPEED * speed,
+            home_y:          HOME_Y,
+            defensive_y:     DEFENSIVE_Y,
+            attack_reach:    f32::INFINITY,
+            bank_offset:     0.0,
+        };
+        match personality {
+            Personality::Balanced => {}
+            Personality::Attacker => {
+                profile.home_y = HOME_Y * 1.6;
+                profile.chase_speed *= 1.15;
+            }
+            Personality::Defender => {
+                profile.home_y = DEFENSIVE_Y;
+                profile.attack_reach = HOME_Y * 1.8;
+            }
+            Personality::BankShot => {
+                profile.bank_offset = BLOCK_DISTANCE * 0.4;
+            }
+        }
+        profile
+    }
+}
+
+/// The built-in opponent. Re-plans every `THINK_INTERVAL`, chasing slow pucks in
+/// its half, intercepting incoming shots and drifting home otherwise. Speeds are
+/// pixels per 60 Hz tick.
+pub struct AiController {
+    /// 0 plays the bottom (host) half, 1 the top (client) half.
+    player:      usize,
+    profile:     AiProfile,
+    pos:         [f32; 2],
+    goal:        [f32; 2],
+    speed:       f32,
+    think_timer: f32,
+    rng:         u32,
+}
+
+impl AiController {
+    pub fn new(player: usize, view: &GameState) -> Self {
+        Self::with_profile(player, view, AiProfile::default())
+    }
+
+    pub fn with_profile(player: usize, view: &GameState, profile: AiProfile) -> Self {
+        let paddle = if player == 0 { &view.host_paddle } else { &view.client_paddle };
+        let pos = [paddle.x, paddle.y];
+        Self {
+            player,
+            profile,
+            pos,
+            goal: pos,
+            speed: profile.return_speed,
+            think_timer: 0.0,
+            rng: 0x9e37_79b9 ^ (player as u32 + 1),
+        }
+    }
+
+    /// Uniform noise in `[-1, 1]` from a xorshift, so matches are reproducible.
+    fn noise(&mut self) -> f32 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 17;
+        self.rng ^= self.rng << 5;
+        (self.rng as f32 / u32::MAX as f32) * 2.0 - 1.0
+    }
+
+    /// Maps a y coordinate into this controller's frame, where its goal is at y = 0.
+    fn own_y(&self, y: f32, view: &GameState) -> f32 {
+        if self.player == 1 { y } else { view.geometry.height - y }
+    }
+
+    fn think(&mut self, view: &GameState) {
+        let prof = self.profile;
+        let geo = &view.geometry;
+        let p = &view.puck;
+        let mid = geo.height / 2.0;
+        let py = self.own_y(p.y, view);
+        // Positive when the puck travels towards our goal.
+        let approach = if self.player == 1 { -p.vy } else { p.vy };
+
+        let (gx, gy, speed) = if py <= mid.min(prof.attack_reach) && approach < 30.0 {
+            // Loose puck on our side: get behind it and strike towards the far goal,
+            // off-centre towards the nearer side wall when banking.
+            let side = if p.x < geo.width / 2.0 { 1.0 } else { -1.0 };
+            (p.x + side * prof.bank_offset, py - BLOCK_DISTANCE * 0.5, prof.chase_speed)
+        } else if approach > 30.0 && py < mid * 1.2 {
+            // Incoming shot: meet it where its predicted path (walls and corners
+            // included) crosses our blocking line.
+            let gy = (py - BLOCK_DISTANCE).max(prof.defensive_y).min(prof.attack_reach);
+            let path = predict_trajectory(p, geo, &view.params, INTERCEPT_HORIZON);
+            let gx = crossing_at_y(p, &path, self.own_y(gy, view))
+                .map(|c| c.x)
+                .unwrap_or(p.x + p.vx * PREDICTION_TIME);
+            (gx, gy, prof.intercept_speed)
+        } else if approach > 0.0 || prof.attack_reach.is_finite() {
+            // Play heading our way (or a goalie idling): shadow it in front of goal.
+            (p.x, prof.defensive_y, prof.return_speed)
+        } else {
+            (geo.width / 2.0, prof.home_y, prof.return_speed)
+        };
+
+        let gx = gx + self.noise() * prof.aim_error[0];
+        let gy = gy + self.noise() * prof.aim_error[1];
+        self.goal = [gx, self.own_y(gy, view)];
+        self.speed = speed;
+    }
+}
+
+impl Controller for AiController {
+    fn target(&mut self, view: &GameState, dt: f32) -> [f32; 2] {
+        self.think_timer -= dt;
+        if self.think_timer <= 0.0 {
+            self.think_timer += self.profile.think_interval;
+            self.think(view);
+        }
+
+        let max_step = self.speed * dt * 60.0;
+        for i in 0..2 {
+            let step = (self.goal[i] - self.pos[i]) * self.profile.reaction_lerp;
+            self.pos[i] += step.clamp(-max_step, max_step);
+        }
+
+        let geo = &view.geometry;
+        let (par, mid) = (geo.paddle_radius, geo.height / 2.0);
+        self.pos[0] = self.pos[0].clamp(par, geo.width - par);
+        self.pos[1] = if self.player == 1 {
+            self.pos[1].clamp(par, mid - par / 2.0)
+        } else {
+            self.pos[1].clamp(mid + par / 2.0, geo.height - par)
+        };
+        self.pos
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::GameEventKind;
+    use crate::physics::Puck;
+
+    #[test]
+    fn test_ai_stays_in_own_half() {
+        let mut gs = GameState::new();
+        let mut ai = AiController::new(1, &gs);
+        let host = [gs.host_paddle.x, gs.host_paddle.y];
+        let dt = 1.0 / 60.0;
+        for _ in 0..600 {
+            let t = ai.target(&gs, dt);
+            assert!(t[1] <= gs.geometry.height / 2.0);
+            gs.server_update(dt, host, t);
+        }
+    }
+
+    #[test]
+    fn test_ai_strikes_loose_puck_in_its_half() {
+        let mut gs = GameState::new();
+        gs.countdown = 0.0;
+        gs.puck = Puck::new(140.0, 200.0, 0.0, 0.0);
+        let mut ai = AiController::new(1, &gs);
+        let host = [gs.host_paddle.x, gs.host_paddle.y];
+        let dt = 1.0 / 60.0;
+        for _ in 0..180 {
+            let t = ai.target(&gs, dt);
+            gs.server_update(dt, host, t);
+        }
+        assert!(gs.events.iter().any(|e| matches!(e.kind, GameEventKind::PaddleHit { player: 1, .. })));
+    }
+
+    #[test]
+    fn test_host_side_ai_mirrors() {
+        let gs = GameState::new();
+        let mut ai = AiController::new(0, &gs);
+        let t = ai.target(&gs, 1.0 / 60.0);
+        assert!(t[1] > gs.geometry.height / 2.0);
+    }
+
+    #[test]
+    fn test_parse_names() {
+        for d in [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard, Difficulty::Expert] {
+            assert_eq!(format!("{:?}", d).parse(), Ok(d));
+        }
+        assert_eq!("BankShot".parse(), Ok(Personality::BankShot));
+        assert!("Impossible".parse::<Difficulty>().is_err());
+    }
+
+    fn play_match(host: AiProfile, client: AiProfile) -> GameState {
+        let mut gs = GameState::new();
+        let mut a = AiController::with_profile(0, &gs, host);
+        let mut b = AiController::with_profile(1, &gs, client);
+        let dt = 1.0 / 60.0;
+        for _ in 0..60 * 300 {
+            let (ta, tb) = (a.target(&gs, dt), b.target(&gs, dt));
+            gs.server_update(dt, ta, tb);
+            if gs.game_over {
+                break;
+            }
+        }
+        gs
+    }
+
+    #[test]
+    fn test_expert_beats_easy() {
+        let easy = AiProfile::new(Difficulty::Easy, Personality::Balanced);
+        let expert = AiProfile::new(Difficulty::Expert, Personality::Balanced);
+        let gs = play_match(easy, expert);
+        assert!(gs.game_over);
+        assert_eq!(gs.winner, Some(1));
+    }
+
+    #[test]
+    fn test_defender_stays_near_goal() {
+        let mut gs = GameState::new();
+        gs.countdown = 0.0;
+        let profile = AiProfile::new(Difficulty::Normal, Personality::Defender);
+        let mut ai = AiController::with_profile(1, &gs, profile);
+        let host = [gs.host_paddle.x, gs.host_paddle.y];
+        let dt = 1.0 / 60.0;
+        gs.puck = Puck::new(120.0, 300.0, 0.0, 0.0);
+        for _ in 0..120 {
+            let t = ai.target(&gs, dt);
+            assert!(t[1] <= profile.attack_reach + profile.aim_error[1] + gs.geometry.paddle_radius);
+            gs.server_update(dt, host, t);
+        }
+    }
+}
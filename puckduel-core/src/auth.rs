@@ -0,0 +1,131 @@
+use std::fmt;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use crate::crypto::Cipher;
+
+pub const KEY_LEN: usize = 32;
+/// HMAC-SHA256 truncated to 128 bits, appended after the payload. `decode`
+/// ignores bytes past the header's length, so tagged packets still parse.
+pub const TAG_LEN: usize = 16;
+
+/// Per-match secret the server issues with `KEY:` at START (LAN players agree
+/// on one in their handshake). Game packets carry a tag under it, so input,
+/// state or GAME_OVER injected by anyone outside the match is dropped.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SessionKey([u8; KEY_LEN]);
+
+impl SessionKey {
+    pub fn from_bytes(bytes: [u8; KEY_LEN]) -> Self {
+        Self(bytes)
+    }
+
+    /// Parses the hex form sent in `KEY:` commands.
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        let hex = hex.trim().as_bytes();
+        if hex.len() != KEY_LEN * 2 {
+            return None;
+        }
+        let mut bytes = [0u8; KEY_LEN];
+        for (b, pair) in bytes.iter_mut().zip(hex.chunks(2)) {
+            *b = u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()?;
+        }
+        Some(Self(bytes))
+    }
+
+    pub fn to_hex(&self) -> String {
+        self.0.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    /// The cipher for direct traffic between the two players, who both hold
+    /// this key; the host and the joiner send in opposite directions. `salt`
+    /// is fresh for every connection (the server's `SALT:`), so a rejoin never
+    /// reuses a nonce under the same key and starts with an empty replay window.
+    pub fn cipher(&self, host: bool, salt: &[u8]) -> Cipher {
+        let mut label = b"puckduel p2p ".to_vec();
+        label.extend_from_slice(salt);
+        Cipher::derive(&self.0, &label, host)
+    }
+
+    fn mac(&self) -> Hmac<Sha256> {
+        Hmac::new_from_slice(&self.0).expect("HMAC takes keys of any length")
+    }
+
+    /// `packet` with its tag appended.
+    pub fn sign(&self, packet: &[u8]) -> Vec<u8> {
+        let mut mac = self.mac();
+        mac.update(packet);
+        let tag = mac.finalize().into_bytes();
+        let mut out = Vec::with_capacity(packet.len() + TAG_LEN);
+        out.extend_from_slice(packet);
+        out.extend_from_slice(&tag[..TAG_LEN]);
+        out
+    }
+
+    /// Checks the tag at the end of `data` (in constant time) and returns the
+    /// packet in front of it, or `None` if it is missing or wrong.
+    pub fn verify<'a>(&self, data: &'a [u8]) -> Option<&'a [u8]> {
+        let (packet, tag) = data.split_at(data.len().checked_sub(TAG_LEN)?);
+        let mut mac = self.mac();
+        mac.update(packet);
+        mac.verify_truncated_left(tag).ok()?;
+        Some(packet)
+    }
+}
+
+impl fmt::Debug for SessionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SessionKey(..)")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{self, MessageType};
+
+    #[test]
+    fn test_signed_packets_verify_and_still_decode() {
+        let key = SessionKey::from_bytes([7; KEY_LEN]);
+        let packet = protocol::encode_input(42, [100.0, 200.0]);
+        let signed = key.sign(&packet);
+        assert_eq!(signed.len(), packet.len() + TAG_LEN);
+        assert_eq!(key.verify(&signed), Some(&packet[..]));
+        assert_eq!(protocol::decode(&signed).unwrap().header.kind, MessageType::Input);
+        assert_eq!(SessionKey::from_hex(&key.to_hex()), Some(key));
+    }
+
+    #[test]
+    fn test_rejects_forged_tampered_and_untagged_packets() {
+        let key = SessionKey::from_bytes([7; KEY_LEN]);
+        let other = SessionKey::from_bytes([8; KEY_LEN]);
+        let packet = protocol::encode_command("GAME_OVER");
+        let mut signed = key.sign(&packet);
+
+        assert_eq!(key.verify(&other.sign(&packet)), None);
+        assert_eq!(key.verify(&packet), None);
+        assert_eq!(key.verify(&[]), None);
+        signed[protocol::HEADER_LEN] ^= 1;
+        assert_eq!(key.verify(&signed), None);
+        assert_eq!(SessionKey::from_hex("abc"), None);
+        assert_eq!(SessionKey::from_hex(&"zz".repeat(KEY_LEN)), None);
+    }
+
+    #[test]
+    fn test_reconnected_p2p_link_is_keyed_afresh() {
+        let key = SessionKey::from_bytes([7; KEY_LEN]);
+        let (host, joiner) = (key.cipher(true, b"first"), key.cipher(false, b"first"));
+        let packet = protocol::encode_input(1, [100.0, 200.0]);
+        let old: Vec<Vec<u8>> = (0..3).map(|_| host.seal(&packet)).collect();
+        for sealed in &old {
+            assert!(joiner.open(protocol::decode(sealed).unwrap().payload).is_some());
+        }
+
+        // The host rejoins: its counter starts over, under a new salt
+        let (host, joiner) = (key.cipher(true, b"second"), key.cipher(false, b"second"));
+        let sealed = host.seal(&packet);
+        assert_eq!(sealed[..protocol::HEADER_LEN + 8], old[0][..protocol::HEADER_LEN + 8]);
+        assert_ne!(sealed, old[0]);
+        assert_eq!(joiner.open(protocol::decode(&sealed).unwrap().payload), Some(packet));
+        assert_eq!(joiner.open(protocol::decode(&old[1]).unwrap().payload), None);
+    }
+}
@@ -1,3 +1,5 @@
+use serde::{Serialize, Deserialize};
+
 /// Game configuration constants
 pub const TABLE_WIDTH: f32 = 360.0;
 pub const TABLE_HEIGHT: f32 = 640.0;
@@ -14,6 +16,8 @@ pub const PADDLE_POWER: f32 = 1.25;
 pub const WALL_REST: f32 = 0.88;
 pub const FRICTION: f32 = 0.06;
 pub const AUTH_HYSTERESIS: f32 = 12.0;
+/// How far from its own goal line a paddle starts, as a share of the table length.
+pub const PADDLE_START_DEPTH: f32 = 0.1875;
 pub const WINNING_SCORE: u32 = 6;
 pub const COUNTDOWN_DURATION: f32 = 3.0;
 pub const GOAL_COUNTDOWN: f32 = 2.5;
@@ -23,6 +27,101 @@ pub const DEAD_RECKONING_SNAP_THRESHOLD: f32 = 120.0;
 pub const NEAR_MISS_ZONE: f32 = 12.0;
 pub const NEAR_MISS_COOLDOWN_MS: u64 = 2000;
 
+/// Table layout used by the physics step. Defaults to the constants above.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+pub struct TableGeometry {
+    pub width:         f32,
+    pub height:        f32,
+    pub goal_width:    f32,
+    pub corner_radius: f32,
+    pub post_radius:   f32,
+    pub puck_radius:   f32,
+    pub paddle_radius: f32,
+}
+
+impl Default for TableGeometry {
+    fn default() -> Self {
+        Self {
+            width:         TABLE_WIDTH,
+            height:        TABLE_HEIGHT,
+            goal_width:    GOAL_WIDTH,
+            corner_radius: CORNER_RADIUS,
+            post_radius:   GOAL_POST_RADIUS,
+            puck_radius:   PUCK_RADIUS,
+            paddle_radius: PADDLE_RADIUS,
+        }
+    }
+}
+
+impl TableGeometry {
+    /// X coordinate of the left goal post.
+    pub fn goal_x(&self) -> f32 {
+        (self.width - self.goal_width) / 2.0
+    }
+
+    pub fn in_goal_gap(&self, x: f32) -> bool {
+        let gx = self.goal_x();
+        x > gx && x < gx + self.goal_width
+    }
+
+    /// Goal post centres: top-left, top-right, bottom-left, bottom-right.
+    pub fn goal_posts(&self) -> [(f32, f32); 4] {
+        let gx = self.goal_x();
+        let gw = self.goal_width;
+        [(gx, 0.0), (gx + gw, 0.0), (gx, self.height), (gx + gw, self.height)]
+    }
+
+    /// Corner arc centres: top-left, top-right, bottom-left, bottom-right.
+    pub fn corner_centres(&self) -> [(f32, f32); 4] {
+        let (cr, tw, th) = (self.corner_radius, self.width, self.height);
+        [(cr, cr), (tw - cr, cr), (cr, th - cr), (tw - cr, th - cr)]
+    }
+
+    /// Whether `(x, y)` lies in the square of the corner whose arc is centred at `(cx, cy)`.
+    pub fn in_corner_zone(&self, x: f32, y: f32, cx: f32, cy: f32) -> bool {
+        let cr = self.corner_radius;
+        let zone_x = if cx < self.width / 2.0 { x < cr } else { x > self.width - cr };
+        let zone_y = if cy < self.height / 2.0 { y < cr } else { y > self.height - cr };
+        zone_x && zone_y
+    }
+
+    /// Furthest the puck centre may sit from a corner arc centre.
+    pub fn corner_reach(&self) -> f32 {
+        (self.corner_radius - 2.0) - self.puck_radius
+    }
+
+    /// Where `player`'s paddle starts: the host (0) in front of the bottom
+    /// goal, the client in front of the top one.
+    pub fn paddle_start(&self, player: usize) -> [f32; 2] {
+        let depth = self.height * PADDLE_START_DEPTH;
+        [self.width / 2.0, if player == 0 { self.height - depth } else { depth }]
+    }
+}
+
+/// Tunable physics coefficients. Defaults to the constants above.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+pub struct PhysicsParams {
+    pub friction:                f32,
+    pub wall_restitution:        f32,
+    pub paddle_power:            f32,
+    pub paddle_surface_friction: f32,
+    pub max_speed:               f32,
+    pub min_puck_speed:          f32,
+}
+
+impl Default for PhysicsParams {
+    fn default() -> Self {
+        Self {
+            friction:                FRICTION,
+            wall_restitution:        WALL_REST,
+            paddle_power:            PADDLE_POWER,
+            paddle_surface_friction: PADDLE_SURFACE_FRICTION,
+            max_speed:               MAX_SPEED,
+            min_puck_speed:          MIN_PUCK_SPEED,
+        }
+    }
+}
+
 /// AI difficulty settings
 pub mod ai {
     pub const CHASE_SPEED: f32 = 11.0;
@@ -36,6 +135,8 @@ pub mod ai {
     pub const THINK_INTERVAL: f32 = 0.085;
     pub const AIM_ERROR_X: f32 = 11.0;
     pub const AIM_ERROR_Y: f32 = 8.0;
+    /// Seconds of puck path predicted when intercepting a shot.
+    pub const INTERCEPT_HORIZON: f32 = 1.0;
 }
 
 /// Network configuration
@@ -43,7 +144,33 @@ pub mod network {
     pub const MSG_CHANNEL_CAPACITY: usize = 64;
     pub const SOCKET_POLL_INTERVAL_MS: u64 = 8;
     pub const TARGET_FPS: u32 = 60;
-    pub const PROTOCOL_VERSION: u32 = 2;
+    /// Sent in every packet header; bump whenever the wire format changes.
+    pub const PROTOCOL_VERSION: u32 = 11;
+    /// Recent events repeated in every state packet so a dropped packet loses none.
+    pub const EVENT_HISTORY: usize = 24;
+    pub const PING_INTERVAL_MS: u64 = 500;
+    /// A ping unanswered for this long counts as lost.
+    pub const PING_TIMEOUT_MS: u64 = 2000;
+    /// Number of recent pings the loss figure is computed over.
+    pub const PING_LOSS_WINDOW: usize = 20;
+    /// Silence from the other end for this long means it has dropped out.
+    pub const HEARTBEAT_TIMEOUT_MS: u64 = 3000;
+    /// How long a dropped player has to rejoin before the match is forfeited.
+    pub const RECONNECT_GRACE_SECS: u64 = 20;
+    /// How often `REJOIN` is resent while the other end is silent.
+    pub const REJOIN_INTERVAL_MS: u64 = 1000;
+    /// P2P connectivity checks: first retry delay, the cap it doubles up to,
+    /// and how many probes each candidate gets before it is given up on.
+    pub const PROBE_INITIAL_MS: u64 = 100;
+    pub const PROBE_MAX_MS: u64 = 1600;
+    pub const PROBE_ATTEMPTS: u32 = 10;
+    /// UDP port LAN hosts broadcast their `Announce` to and browsers listen on.
+    pub const LAN_DISCOVERY_PORT: u16 = 9877;
+    pub const LAN_ANNOUNCE_INTERVAL_MS: u64 = 1000;
+    /// How long `discover_lan` listens; long enough to catch each host's announce.
+    pub const LAN_DISCOVERY_WINDOW_MS: u64 = 2500;
+    /// Longest room name a LAN host may announce.
+    pub const LAN_NAME_MAX_CHARS: usize = 32;
 }
 
 /// Interpolation configuration
@@ -56,6 +183,17 @@ pub mod interpolation {
     pub const ADAPTIVE_ERROR_THRESHOLD: f32 = 100.0;
     pub const HANDOFF_BLEND: f32 = 0.50;
     pub const DEAD_RECKONING_SNAP_THRESHOLD: f32 = super::DEAD_RECKONING_SNAP_THRESHOLD;
+    /// Bounds on how far in the past (seconds) remote snapshots are rendered.
+    pub const MIN_RENDER_DELAY: f32 = 0.020;
+    pub const MAX_RENDER_DELAY: f32 = 0.150;
+    pub const SNAPSHOT_BUFFER_LEN: usize = 32;
+}
+
+pub mod rollback {
+    /// How far back (in 60 Hz frames) a late remote input can rewind the simulation.
+    pub const MAX_ROLLBACK_FRAMES: usize = 15;
+    /// Remote pointer error (pixels) below which a prediction counts as correct.
+    pub const PREDICTION_TOLERANCE: f32 = 0.5;
 }
 
 pub mod audio {
@@ -63,6 +201,7 @@ pub mod audio {
 }
 
 #[cfg(test)]
+#[allow(clippy::assertions_on_constants)]
 mod tests {
     use super::*;
 
@@ -97,4 +236,26 @@ mod tests {
         assert!(WINNING_SCORE >= 3);
         assert!(WINNING_SCORE <= 21);
     }
+
+    #[test]
+    fn test_default_geometry_matches_constants() {
+        let geo = TableGeometry::default();
+        assert_eq!(geo.goal_x(), (TABLE_WIDTH - GOAL_WIDTH) / 2.0);
+        assert!(geo.in_goal_gap(TABLE_WIDTH / 2.0));
+        assert!(!geo.in_goal_gap(geo.goal_x() - 1.0));
+        assert!(geo.corner_reach() > 0.0);
+        assert_eq!(PhysicsParams::default().max_speed, MAX_SPEED);
+        assert_eq!(geo.paddle_start(0), [TABLE_WIDTH / 2.0, TABLE_HEIGHT - 120.0]);
+        assert_eq!(geo.paddle_start(1), [TABLE_WIDTH / 2.0, 120.0]);
+    }
+
+    #[test]
+    fn test_paddles_start_in_their_own_half_on_any_table() {
+        let geo = TableGeometry { width: 600.0, height: 1000.0, ..Default::default() };
+        let [hx, hy] = geo.paddle_start(0);
+        let [cx, cy] = geo.paddle_start(1);
+        assert_eq!((hx, cx), (300.0, 300.0));
+        assert_eq!(hy, 1000.0 - cy);
+        assert!(cy > geo.paddle_radius && cy < 500.0);
+    }
 }
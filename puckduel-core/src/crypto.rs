@@ -0,0 +1,219 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use hkdf::Hkdf;
+use sha2::Sha256;
+use x25519_dalek::{PublicKey, StaticSecret};
+use crate::protocol::{self, MessageType};
+
+pub const PUBLIC_KEY_LEN: usize = 32;
+/// Nonce counter in front of the ciphertext.
+const COUNTER_LEN: usize = 8;
+/// How far behind the newest counter a packet may arrive and still be opened.
+const REPLAY_WINDOW: u64 = 64;
+
+/// One side of an X25519 key exchange, made fresh for every connection. It is
+/// unauthenticated, like Noise's NN pattern: enough to keep room codes, peer
+/// addresses and match data from anyone listening on the network, while
+/// tampering is caught by the AEAD and the session key's tags.
+pub struct Handshake {
+    secret: StaticSecret,
+    public: PublicKey,
+}
+
+impl Handshake {
+    /// From 32 random bytes; the caller brings the randomness.
+    pub fn new(random: [u8; 32]) -> Self {
+        let secret = StaticSecret::from(random);
+        let public = PublicKey::from(&secret);
+        Self { secret, public }
+    }
+
+    pub fn public(&self) -> [u8; PUBLIC_KEY_LEN] {
+        self.public.to_bytes()
+    }
+
+    /// A `Handshake` packet carrying our public key.
+    pub fn packet(&self) -> Vec<u8> {
+        protocol::encode(MessageType::Handshake, 0, &self.public())
+    }
+
+    /// The secret shared with the owner of `their_public`, or `None` for a
+    /// malformed or low-order key.
+    pub fn agree(&self, their_public: &[u8]) -> Option<[u8; 32]> {
+        let theirs: [u8; PUBLIC_KEY_LEN] = their_public.try_into().ok()?;
+        let shared = self.secret.diffie_hellman(&PublicKey::from(theirs));
+        shared.was_contributory().then(|| shared.to_bytes())
+    }
+
+    /// The channel to the owner of `their_public`. The side that sent its key
+    /// first is the `initiator`.
+    pub fn finish(&self, their_public: &[u8], initiator: bool) -> Option<Cipher> {
+        Some(Cipher::derive(&self.agree(their_public)?, b"puckduel transport", initiator))
+    }
+}
+
+/// ChaCha20-Poly1305 over whole packets. Each direction has its own nonce
+/// space (first nonce byte), filled by a send counter that travels with the
+/// packet, so the two ends of a channel never reuse a nonce. Each counter is
+/// opened at most once, so a captured packet cannot be replayed.
+pub struct Cipher {
+    aead:     ChaCha20Poly1305,
+    /// Nonce prefix for what we send; the other end sends with the opposite one.
+    send_dir: u8,
+    sent:     AtomicU64,
+    received: Mutex<ReplayWindow>,
+}
+
+/// The counters opened so far: anything `REPLAY_WINDOW` or more behind the
+/// newest is refused, the rest is tracked in a bitmap.
+#[derive(Default)]
+struct ReplayWindow {
+    /// One past the newest counter opened.
+    top:  u64,
+    /// Bit `i` set if counter `top - 1 - i` was opened.
+    seen: u64,
+}
+
+impl ReplayWindow {
+    fn is_fresh(&self, counter: u64) -> bool {
+        if counter >= self.top {
+            return true;
+        }
+        let age = self.top - 1 - counter;
+        age < REPLAY_WINDOW && self.seen & (1 << age) == 0
+    }
+
+    fn mark(&mut self, counter: u64) {
+        if counter >= self.top {
+            let shift = counter + 1 - self.top;
+            self.seen = if shift >= REPLAY_WINDOW { 0 } else { self.seen << shift };
+            self.seen |= 1;
+            self.top = counter + 1;
+        } else {
+            self.seen |= 1 << (self.top - 1 - counter);
+        }
+    }
+}
+
+impl Cipher {
+    /// Keyed from `secret` with HKDF-SHA256 under `label`. The two ends must
+    /// pass opposite `initiator`s.
+    pub fn derive(secret: &[u8], label: &[u8], initiator: bool) -> Self {
+        let mut key = Key::default();
+        Hkdf::<Sha256>::new(None, secret)
+            .expand(label, &mut key)
+            .expect("a 32-byte key is a valid HKDF output length");
+        Self {
+            aead:     ChaCha20Poly1305::new(&key),
+            send_dir: u8::from(!initiator),
+            sent:     AtomicU64::new(0),
+            received: Mutex::new(ReplayWindow::default()),
+        }
+    }
+
+    fn nonce(dir: u8, counter: u64) -> Nonce {
+        let mut nonce = Nonce::default();
+        nonce[0] = dir;
+        nonce[4..].copy_from_slice(&counter.to_le_bytes());
+        nonce
+    }
+
+    /// `packet` encrypted into a `Sealed` packet.
+    pub fn seal(&self, packet: &[u8]) -> Vec<u8> {
+        let counter = self.sent.fetch_add(1, Ordering::Relaxed);
+        let ciphertext = self
+            .aead
+            .encrypt(&Self::nonce(self.send_dir, counter), packet)
+            .expect("ChaCha20-Poly1305 encrypts any datagram-sized buffer");
+        let mut payload = Vec::with_capacity(COUNTER_LEN + ciphertext.len());
+        payload.extend_from_slice(&counter.to_le_bytes());
+        payload.extend_from_slice(&ciphertext);
+        protocol::encode(MessageType::Sealed, 0, &payload)
+    }
+
+    /// The packet inside a `Sealed` payload from the other end, or `None` if it
+    /// was not sealed for this channel, was altered on the way, or was opened
+    /// before (or is too old to tell).
+    pub fn open(&self, payload: &[u8]) -> Option<Vec<u8>> {
+        let (counter, ciphertext) = payload.split_at_checked(COUNTER_LEN)?;
+        let counter = u64::from_le_bytes(counter.try_into().ok()?);
+        let mut received = self.received.lock().unwrap();
+        if !received.is_fresh(counter) {
+            return None;
+        }
+        // Only an authentic packet moves the window, so forged counters cannot
+        // push real traffic out of it
+        let packet = self.aead.decrypt(&Self::nonce(1 - self.send_dir, counter), ciphertext).ok()?;
+        received.mark(counter);
+        Some(packet)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channel() -> (Cipher, Cipher) {
+        let (client, server) = (Handshake::new([1; 32]), Handshake::new([2; 32]));
+        (client.finish(&server.public(), true).unwrap(), server.finish(&client.public(), false).unwrap())
+    }
+
+    #[test]
+    fn test_handshake_gives_both_ends_the_same_channel() {
+        let (client, server) = channel();
+        let packet = protocol::encode_command("JOIN:1234");
+        let sealed = client.seal(&packet);
+        let outer = protocol::decode(&sealed).unwrap();
+        assert_eq!(outer.header.kind, MessageType::Sealed);
+        assert!(!sealed.windows(4).any(|w| w == b"1234"));
+        assert_eq!(server.open(outer.payload), Some(packet.clone()));
+
+        let reply = server.seal(&packet);
+        assert_eq!(client.open(protocol::decode(&reply).unwrap().payload), Some(packet));
+    }
+
+    #[test]
+    fn test_open_rejects_tampering_and_reflection() {
+        let (client, server) = channel();
+        let sealed = client.seal(&protocol::encode_command("START"));
+        let payload = protocol::decode(&sealed).unwrap().payload.to_vec();
+
+        let mut tampered = payload.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert_eq!(server.open(&tampered), None);
+        // Our own packet bounced back is in the wrong direction
+        assert_eq!(client.open(&payload), None);
+        assert_eq!(server.open(&payload[..4]), None);
+
+        let stranger = Handshake::new([3; 32]).finish(&Handshake::new([2; 32]).public(), false).unwrap();
+        assert_eq!(stranger.open(&payload), None);
+        assert_eq!(Handshake::new([1; 32]).agree(&[0; PUBLIC_KEY_LEN]), None);
+    }
+
+    #[test]
+    fn test_open_refuses_replays_but_allows_reordering() {
+        let (client, server) = channel();
+        let sealed: Vec<Vec<u8>> = (0..100)
+            .map(|i| protocol::decode(&client.seal(&protocol::encode_command(&format!("PING:{}", i)))).unwrap().payload.to_vec())
+            .collect();
+
+        assert!(server.open(&sealed[1]).is_some());
+        assert!(server.open(&sealed[0]).is_some(), "late but within the window");
+        assert_eq!(server.open(&sealed[0]), None);
+        assert_eq!(server.open(&sealed[1]), None);
+
+        assert!(server.open(&sealed[99]).is_some());
+        assert!(server.open(&sealed[99 - REPLAY_WINDOW as usize + 1]).is_some());
+        // Never opened, but too far behind to tell
+        assert_eq!(server.open(&sealed[99 - REPLAY_WINDOW as usize]), None);
+        assert_eq!(server.open(&sealed[99]), None);
+
+        // A forged packet with a far-ahead counter does not move the window
+        let mut forged = sealed[50].clone();
+        forged[..COUNTER_LEN].copy_from_slice(&1_000u64.to_le_bytes());
+        assert_eq!(server.open(&forged), None);
+        assert!(server.open(&sealed[50]).is_some());
+    }
+}
@@ -0,0 +1,32 @@
+use serde::{Serialize, Deserialize};
+
+/// Which boundary of the table the puck struck.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub enum WallSide {
+    Left,
+    Right,
+    Top,
+    Bottom,
+    Corner,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+pub enum GameEventKind {
+    PaddleHit { player: usize, impulse: f32, point: [f32; 2] },
+    WallHit { speed: f32, side: WallSide },
+    PostHit { speed: f32 },
+    Goal { scorer: usize, shot_speed: f32 },
+    CountdownStarted { seconds: f32 },
+    MatchOver { winner: Option<usize> },
+}
+
+/// A gameplay event. Ids count up separately for each origin (the slot of the
+/// peer whose simulation produced it), so the two peers never hand out the same
+/// one; receivers drop anything at or below the last id they handled from that
+/// origin.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+pub struct GameEvent {
+    pub id:     u64,
+    pub origin: usize,
+    pub kind:   GameEventKind,
+}
@@ -1,14 +1,9 @@
 use serde::{Serialize, Deserialize};
 use crate::config::*;
 use crate::physics::*;
-
-pub const TW: f32 = TABLE_WIDTH;
-pub const TH: f32 = TABLE_HEIGHT;
-pub const PR: f32 = PUCK_RADIUS;
-pub const PAR: f32 = PADDLE_RADIUS;
-pub const GOAL_W: f32 = GOAL_WIDTH;
-pub const GX: f32 = (TW - GOAL_W) / 2.0;
-pub const CR: f32 = CORNER_RADIUS;
+use crate::rules::{MatchRules, TimeUp};
+use crate::events::{GameEvent, GameEventKind};
+use crate::config::network::EVENT_HISTORY;
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct RenderState {
@@ -22,13 +17,24 @@ pub struct RenderState {
     pub wall_flash:    f32,
     pub goal_flash:    f32,
     pub score_flash:   [f32; 2],
-    pub hit:           u8,
-    pub wall_hit:      u8,
-    pub goal_scored:   u8,
+    /// The most recent events, oldest first; repeated every frame for redundancy.
+    pub events:        Vec<GameEvent>,
     pub countdown:     f32,
     pub game_over:     bool,
+    pub rules:         MatchRules,
+    pub clock:         f32,
+    pub sudden_death:  bool,
+    pub game:          u32,
+    pub games_won:     [u32; 2],
+    pub winner:        Option<usize>,
+    pub forfeit:       bool,
+    /// The table and tuning the authoritative side simulates with, so the
+    /// passive one predicts the puck on the same table.
+    pub geometry:      TableGeometry,
+    pub params:        PhysicsParams,
 }
 
+#[derive(Clone)]
 pub struct GameState {
     pub puck:          Puck,
     pub host_paddle:   Paddle,
@@ -37,62 +43,160 @@ pub struct GameState {
     pub wall_flash:    f32,
     pub goal_flash:    f32,
     pub score_flash:   [f32; 2],
-    pub hit:           u8,
-    pub wall_hit:      u8,
-    pub goal_scored:   u8,
+    /// Up to `EVENT_HISTORY` most recent events, oldest first.
+    pub events:        Vec<GameEvent>,
+    /// Per origin, the id the next event will take: our own counter, and one
+    /// past the newest event merged from the other peer.
+    pub next_event_id: [u64; 2],
+    /// Origin stamped on the events this state emits: the local player's slot
+    /// in a split-authority match, 0 on the server.
+    pub event_origin:  usize,
     pub countdown:     f32,
     pub game_over:     bool,
+    pub geometry:      TableGeometry,
+    pub params:        PhysicsParams,
+    pub rules:         MatchRules,
+    /// Seconds left in the current game when `rules.time_limit` is set.
+    pub clock:         f32,
+    pub sudden_death:  bool,
+    /// 1-based index of the game being played within the series.
+    pub game:          u32,
+    pub games_won:     [u32; 2],
+    /// Match winner once `game_over` is set; `None` for a drawn match.
+    pub winner:        Option<usize>,
+    /// The match ended because the loser dropped out and never came back.
+    pub forfeit:       bool,
+}
+
+impl Default for GameState {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl GameState {
     pub fn new() -> Self {
+        Self::with_table(TableGeometry::default(), PhysicsParams::default())
+    }
+
+    /// A fresh game on a custom table with custom physics tuning.
+    pub fn with_table(geometry: TableGeometry, params: PhysicsParams) -> Self {
+        let (tw, th) = (geometry.width, geometry.height);
+        let ([hx, hy], [cx, cy]) = (geometry.paddle_start(0), geometry.paddle_start(1));
         Self {
-            puck:          Puck::new(TW / 2.0, TH / 2.0, 0.0, 0.0),
-            host_paddle:   Paddle::new(TW / 2.0, TH - 120.0),
-            client_paddle: Paddle::new(TW / 2.0, 120.0),
+            puck:          Puck::new(tw / 2.0, th / 2.0, 0.0, 0.0),
+            host_paddle:   Paddle::new(hx, hy),
+            client_paddle: Paddle::new(cx, cy),
             score:         [0, 0],
             wall_flash:    0.0,
             goal_flash:    0.0,
             score_flash:   [0.0; 2],
-            hit:           0,
-            wall_hit:      0,
-            goal_scored:   0,
+            events:        vec![GameEvent { id: 0, origin: 0, kind: GameEventKind::CountdownStarted { seconds: COUNTDOWN_DURATION } }],
+            next_event_id: [1, 0],
+            event_origin:  0,
             countdown:     COUNTDOWN_DURATION,
             game_over:     false,
+            geometry,
+            params,
+            rules:         MatchRules::default(),
+            clock:         0.0,
+            sudden_death:  false,
+            game:          1,
+            games_won:     [0, 0],
+            winner:        None,
+            forfeit:       false,
         }
     }
 
+    /// Replaces the match rules and restarts the match clock.
+    pub fn set_rules(&mut self, rules: MatchRules) {
+        self.rules = rules;
+        self.clock = rules.time_limit.unwrap_or(0.0);
+    }
+
     fn reset_puck(&mut self, loser: Option<usize>) {
+        let (tw, th) = (self.geometry.width, self.geometry.height);
         match loser {
             Some(0) => {
-                self.puck = Puck::new(TW / 2.0, TH * 0.75, 0.0, 0.0);
+                self.puck = Puck::new(tw / 2.0, th * 0.75, 0.0, 0.0);
             }
             Some(1) => {
-                self.puck = Puck::new(TW / 2.0, TH * 0.25, 0.0, 0.0);
+                self.puck = Puck::new(tw / 2.0, th * 0.25, 0.0, 0.0);
             }
             _ => {
-                self.puck = Puck::new(TW / 2.0, TH / 2.0, 0.0, 0.0);
+                self.puck = Puck::new(tw / 2.0, th / 2.0, 0.0, 0.0);
+            }
+        }
+    }
+
+    fn emit(&mut self, kind: GameEventKind) {
+        let origin = self.event_origin;
+        self.events.push(GameEvent { id: self.next_event_id[origin], origin, kind });
+        self.next_event_id[origin] += 1;
+        self.trim_events();
+    }
+
+    fn trim_events(&mut self) {
+        let excess = self.events.len().saturating_sub(EVENT_HISTORY);
+        self.events.drain(..excess);
+    }
+
+    /// Turns a physics contact into an event. Wall and post knocks below
+    /// `audio::WALL_HIT_SPEED_THRESHOLD` are too soft to be worth a flash or sound.
+    fn report_contact(&mut self, contact: Contact) {
+        match contact {
+            Contact::Paddle { player, impulse, point } => {
+                self.emit(GameEventKind::PaddleHit { player, impulse, point });
+            }
+            Contact::Wall { side, speed } if speed >= audio::WALL_HIT_SPEED_THRESHOLD => {
+                self.flash_wall(speed);
+                self.emit(GameEventKind::WallHit { speed, side });
+            }
+            Contact::Post { speed } if speed >= audio::WALL_HIT_SPEED_THRESHOLD => {
+                self.flash_wall(speed);
+                self.emit(GameEventKind::PostHit { speed });
+            }
+            Contact::Wall { .. } | Contact::Post { .. } => {}
+        }
+    }
+
+    fn flash_wall(&mut self, speed: f32) {
+        self.wall_flash = self.wall_flash.max((speed / self.params.max_speed).min(1.0));
+    }
+
+    fn start_countdown(&mut self, seconds: f32) {
+        self.countdown = seconds;
+        self.emit(GameEventKind::CountdownStarted { seconds });
+    }
+
+    /// Adopts events produced by the authoritative peer, skipping any already
+    /// held from the same origin.
+    pub fn merge_events(&mut self, events: &[GameEvent]) {
+        for ev in events {
+            let Some(next) = self.next_event_id.get_mut(ev.origin) else { continue };
+            if ev.id >= *next {
+                *next = ev.id + 1;
+                self.events.push(*ev);
             }
         }
+        self.trim_events();
     }
 
     /// Server-side update: takes both paddle positions directly and runs physics authoritatively.
     pub fn server_update(&mut self, dt: f32, host_ptr: [f32; 2], client_ptr: [f32; 2]) {
-        self.hit = 0;
-        self.wall_hit = 0;
-        self.goal_scored = 0;
-
         // Countdown
         if self.countdown > 0.0 {
             self.countdown -= dt;
             if self.countdown < 0.0 { self.countdown = 0.0; }
         }
 
+        let (tw, th, par) = (self.geometry.width, self.geometry.height, self.geometry.paddle_radius);
+
         // Update host paddle (bottom)
         let old_hx = self.host_paddle.x;
         let old_hy = self.host_paddle.y;
-        self.host_paddle.x = host_ptr[0].clamp(PAR, TW - PAR);
-        self.host_paddle.y = host_ptr[1].clamp(TH / 2.0 + PAR / 2.0, TH - PAR);
+        self.host_paddle.x = host_ptr[0].clamp(par, tw - par);
+        self.host_paddle.y = host_ptr[1].clamp(th / 2.0 + par / 2.0, th - par);
         let inv_dt = if dt > 0.001 { 1.0 / dt } else { 60.0 };
         self.host_paddle.pvx = (self.host_paddle.x - old_hx) * inv_dt;
         self.host_paddle.pvy = (self.host_paddle.y - old_hy) * inv_dt;
@@ -100,84 +204,69 @@ impl GameState {
         // Update client paddle (top)
         let old_cx = self.client_paddle.x;
         let old_cy = self.client_paddle.y;
-        self.client_paddle.x = client_ptr[0].clamp(PAR, TW - PAR);
-        self.client_paddle.y = client_ptr[1].clamp(PAR, TH / 2.0 - PAR / 2.0);
+        self.client_paddle.x = client_ptr[0].clamp(par, tw - par);
+        self.client_paddle.y = client_ptr[1].clamp(par, th / 2.0 - par / 2.0);
         self.client_paddle.pvx = (self.client_paddle.x - old_cx) * inv_dt;
         self.client_paddle.pvy = (self.client_paddle.y - old_cy) * inv_dt;
 
-        // Physics substeps (120Hz) — skip during countdown
+        // Physics substeps (120Hz) — skip during countdown. Paddles move linearly
+        // from their previous position so the sweep sees the whole swipe.
         if self.countdown <= 0.0 {
         let substeps = 4;
         let sub_dt = dt / (substeps as f32);
-        for _ in 0..substeps {
-            self.puck.x += self.puck.vx * sub_dt;
-            self.puck.y += self.puck.vy * sub_dt;
-
-            apply_friction(&mut self.puck, sub_dt);
-
-            // Corner collisions
-            let (px, py) = (self.puck.x, self.puck.y);
-            collide_corner_puck(&mut self.puck, CR, CR, px < CR && py < CR);
-            collide_corner_puck(&mut self.puck, TW - CR, CR, px > TW - CR && py < CR);
-            collide_corner_puck(&mut self.puck, CR, TH - CR, px < CR && py > TH - CR);
-            collide_corner_puck(&mut self.puck, TW - CR, TH - CR, px > TW - CR && py > TH - CR);
-
-            // Side walls
-            if self.puck.x - PR < 0.0 {
-                self.puck.x = PR;
-                self.puck.vx = -self.puck.vx * WALL_REST;
-            } else if self.puck.x + PR > TW {
-                self.puck.x = TW - PR;
-                self.puck.vx = -self.puck.vx * WALL_REST;
-            }
+        let host_end = (self.host_paddle.x, self.host_paddle.y);
+        let client_end = (self.client_paddle.x, self.client_paddle.y);
+        for i in 1..=substeps {
+            let f = i as f32 / substeps as f32;
+            self.host_paddle.x = old_hx + (host_end.0 - old_hx) * f;
+            self.host_paddle.y = old_hy + (host_end.1 - old_hy) * f;
+            self.client_paddle.x = old_cx + (client_end.0 - old_cx) * f;
+            self.client_paddle.y = old_cy + (client_end.1 - old_cy) * f;
 
-            // End walls with goal gap
-            let in_gap = self.puck.x > GX && self.puck.x < GX + GOAL_W;
-            if self.puck.y - PR < 0.0 && !in_gap {
-                self.puck.y = PR;
-                self.puck.vy = -self.puck.vy * WALL_REST;
-            } else if self.puck.y + PR > TH && !in_gap {
-                self.puck.y = TH - PR;
-                self.puck.vy = -self.puck.vy * WALL_REST;
-            }
-
-            // Goal posts
-            collide_goal_post(&mut self.puck, GX, 0.0);
-            collide_goal_post(&mut self.puck, GX + GOAL_W, 0.0);
-            collide_goal_post(&mut self.puck, GX, TH);
-            collide_goal_post(&mut self.puck, GX + GOAL_W, TH);
+            apply_friction(&mut self.puck, sub_dt, &self.params);
 
-            // Paddle collisions
-            if collide_paddle_puck(&mut self.puck, &self.host_paddle) { self.hit = 1; }
-            if collide_paddle_puck(&mut self.puck, &self.client_paddle) { self.hit = 1; }
+            let contacts = sweep_puck(&mut self.puck, &[&self.host_paddle, &self.client_paddle], sub_dt, &self.geometry, &self.params);
+            for c in contacts {
+                self.report_contact(c);
+            }
 
-            clamp_max_speed(&mut self.puck);
+            clamp_max_speed(&mut self.puck, &self.params);
         }
         } // end countdown gate
 
         // Goal detection
-        if self.countdown <= 0.0 {
-            if self.puck.y < 0.0 {
-                self.score[0] += 1;
+        if self.countdown <= 0.0 && !self.game_over {
+            let scorer = if self.puck.y < 0.0 {
+                Some(0)
+            } else if self.puck.y > th {
+                Some(1)
+            } else {
+                None
+            };
+            if let Some(scorer) = scorer {
+                self.score[scorer] += 1;
                 self.goal_flash = 1.0;
-                self.score_flash[0] = 1.0;
-                self.goal_scored = 1;
-                if self.score[0] >= WINNING_SCORE {
-                    self.game_over = true;
-                } else {
-                    self.reset_puck(Some(1));
-                    self.countdown = GOAL_COUNTDOWN;
+                self.score_flash[scorer] = 1.0;
+                self.emit(GameEventKind::Goal { scorer, shot_speed: self.puck.speed() });
+                match self.rules.game_winner(self.score, self.sudden_death) {
+                    Some(w) => self.finish_game(Some(w)),
+                    None => {
+                        self.reset_puck(Some(1 - scorer));
+                        self.start_countdown(GOAL_COUNTDOWN);
+                    }
                 }
-            } else if self.puck.y > TH {
-                self.score[1] += 1;
-                self.goal_flash = 1.0;
-                self.score_flash[1] = 1.0;
-                self.goal_scored = 1;
-                if self.score[1] >= WINNING_SCORE {
-                    self.game_over = true;
-                } else {
-                    self.reset_puck(Some(0));
-                    self.countdown = GOAL_COUNTDOWN;
+            }
+        }
+
+        // Match clock — runs only while the puck is live
+        if self.rules.time_limit.is_some() && self.countdown <= 0.0 && !self.sudden_death && !self.game_over {
+            self.clock -= dt;
+            if self.clock <= 0.0 {
+                self.clock = 0.0;
+                match self.rules.time_up(self.score) {
+                    TimeUp::Winner(w) => self.finish_game(Some(w)),
+                    TimeUp::SuddenDeath => self.sudden_death = true,
+                    TimeUp::Draw => self.finish_game(None),
                 }
             }
         }
@@ -189,6 +278,41 @@ impl GameState {
         self.score_flash[1] = (self.score_flash[1] - dt * 1.8).max(0.0);
     }
 
+    /// Records the result of the current game and either ends the match or
+    /// sets up the next game of the series.
+    fn finish_game(&mut self, winner: Option<usize>) {
+        if let Some(w) = winner {
+            self.games_won[w] += 1;
+        }
+        if let Some(w) = self.rules.series_winner(self.games_won) {
+            self.game_over = true;
+            self.winner = Some(w);
+            self.emit(GameEventKind::MatchOver { winner: self.winner });
+        } else if self.game >= self.rules.best_of.max(1) {
+            self.game_over = true;
+            self.winner = self.rules.game_winner(self.games_won, true);
+            self.emit(GameEventKind::MatchOver { winner: self.winner });
+        } else {
+            self.game += 1;
+            self.score = [0, 0];
+            self.sudden_death = false;
+            self.clock = self.rules.time_limit.unwrap_or(0.0);
+            self.reset_puck(None);
+            self.start_countdown(COUNTDOWN_DURATION);
+        }
+    }
+
+    /// Ends the match in `winner`'s favour because the other player left.
+    pub fn forfeit(&mut self, winner: usize) {
+        if self.game_over {
+            return;
+        }
+        self.game_over = true;
+        self.winner = Some(winner);
+        self.forfeit = true;
+        self.emit(GameEventKind::MatchOver { winner: self.winner });
+    }
+
     pub fn to_render(&self) -> RenderState {
         RenderState {
             puck:          [self.puck.x, self.puck.y],
@@ -201,11 +325,179 @@ impl GameState {
             wall_flash:    self.wall_flash,
             goal_flash:    self.goal_flash,
             score_flash:   self.score_flash,
-            hit:           self.hit,
-            wall_hit:      self.wall_hit,
-            goal_scored:   self.goal_scored,
+            events:        self.events.clone(),
             countdown:     self.countdown,
             game_over:     self.game_over,
+            rules:         self.rules,
+            clock:         self.clock,
+            sudden_death:  self.sudden_death,
+            game:          self.game,
+            games_won:     self.games_won,
+            winner:        self.winner,
+            forfeit:       self.forfeit,
+            geometry:      self.geometry,
+            params:        self.params,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::Overtime;
+    use crate::events::WallSide;
+
+    fn idle(gs: &GameState) -> ([f32; 2], [f32; 2]) {
+        (gs.geometry.paddle_start(0), gs.geometry.paddle_start(1))
+    }
+
+    /// Places the puck just past the top goal line so the host scores this tick.
+    fn host_scores(gs: &mut GameState) {
+        let (h, c) = idle(gs);
+        gs.countdown = 0.0;
+        gs.puck = Puck::new(gs.geometry.width / 2.0, -1.0, 0.0, 0.0);
+        gs.server_update(1.0 / 60.0, h, c);
+    }
+
+    #[test]
+    fn test_custom_table_places_paddles_and_travels_in_state() {
+        let geometry = TableGeometry { width: 480.0, height: 900.0, goal_width: 120.0, ..Default::default() };
+        let params = PhysicsParams { max_speed: 1200.0, ..Default::default() };
+        let gs = GameState::with_table(geometry, params);
+        assert_eq!([gs.host_paddle.x, gs.host_paddle.y], geometry.paddle_start(0));
+        assert_eq!([gs.client_paddle.x, gs.client_paddle.y], geometry.paddle_start(1));
+        let state = gs.to_render();
+        assert_eq!(state.geometry, geometry);
+        assert_eq!(state.params, params);
+    }
+
+    #[test]
+    fn test_first_to_target_ends_match() {
+        let mut gs = GameState::new();
+        gs.set_rules(MatchRules { target_score: 2, ..Default::default() });
+        host_scores(&mut gs);
+        assert_eq!(gs.score, [1, 0]);
+        assert!(!gs.game_over);
+        host_scores(&mut gs);
+        assert!(gs.game_over);
+        assert_eq!(gs.winner, Some(0));
+        assert_eq!(gs.to_render().winner, Some(0));
+    }
+
+    #[test]
+    fn test_forfeit_ends_match_once() {
+        let mut gs = GameState::new();
+        host_scores(&mut gs);
+        gs.forfeit(1);
+        assert!(gs.game_over && gs.forfeit);
+        assert_eq!(gs.winner, Some(1));
+        assert_eq!(gs.score, [1, 0]);
+        gs.forfeit(0);
+        assert_eq!(gs.to_render().winner, Some(1));
+        assert!(matches!(gs.events.last().unwrap().kind, GameEventKind::MatchOver { winner: Some(1) }));
+    }
+
+    #[test]
+    fn test_best_of_three_starts_next_game() {
+        let mut gs = GameState::new();
+        gs.set_rules(MatchRules { target_score: 1, best_of: 3, ..Default::default() });
+        host_scores(&mut gs);
+        assert!(!gs.game_over);
+        assert_eq!(gs.game, 2);
+        assert_eq!(gs.score, [0, 0]);
+        assert_eq!(gs.games_won, [1, 0]);
+        host_scores(&mut gs);
+        assert!(gs.game_over);
+        assert_eq!(gs.winner, Some(0));
+    }
+
+    #[test]
+    fn test_clock_expiry_enters_sudden_death() {
+        let mut gs = GameState::new();
+        gs.set_rules(MatchRules { time_limit: Some(0.05), overtime: Overtime::SuddenDeath, ..Default::default() });
+        gs.countdown = 0.0;
+        let (h, c) = idle(&gs);
+        for _ in 0..5 {
+            gs.server_update(1.0 / 60.0, h, c);
+        }
+        assert!(gs.sudden_death);
+        assert!(!gs.game_over);
+        host_scores(&mut gs);
+        assert!(gs.game_over);
+        assert_eq!(gs.winner, Some(0));
+    }
+
+    #[test]
+    fn test_event_ids_are_monotonic() {
+        let mut gs = GameState::new();
+        host_scores(&mut gs);
+        let ids: Vec<u64> = gs.events.iter().map(|e| e.id).collect();
+        assert!(ids.windows(2).all(|w| w[1] > w[0]));
+        assert!(gs.events.iter().any(|e| matches!(e.kind, GameEventKind::Goal { scorer: 0, .. })));
+        assert!(matches!(gs.events.last().unwrap().kind, GameEventKind::CountdownStarted { .. }));
+    }
+
+    #[test]
+    fn test_paddle_hit_event_names_player() {
+        let mut gs = GameState::new();
+        let (h, c) = idle(&gs);
+        gs.countdown = 0.0;
+        gs.puck = Puck::new(h[0], h[1] - 50.0, 0.0, 600.0);
+        gs.server_update(1.0 / 60.0, h, c);
+        assert!(gs.events.iter().any(|e| matches!(e.kind, GameEventKind::PaddleHit { player: 0, .. })));
+    }
+
+    #[test]
+    fn test_merge_events_skips_duplicates_and_keeps_history_bounded() {
+        let mut auth = GameState::new();
+        let mut passive = GameState::new();
+        for _ in 0..EVENT_HISTORY {
+            host_scores(&mut auth);
+            passive.merge_events(&auth.to_render().events);
+            passive.merge_events(&auth.to_render().events);
+        }
+        assert_eq!(passive.next_event_id, auth.next_event_id);
+        assert_eq!(passive.events, auth.events);
+        assert!(passive.events.len() <= EVENT_HISTORY);
+    }
+
+    #[test]
+    fn test_merge_keeps_events_from_both_origins_with_overlapping_ids() {
+        let mut host = GameState::new();
+        let mut client = GameState::new();
+        client.event_origin = 1;
+        // Authority passes to the client before any host state reaches it
+        host_scores(&mut host);
+        let (h, c) = idle(&client);
+        client.countdown = 0.0;
+        client.puck = Puck::new(c[0], c[1] + 50.0, 0.0, -600.0);
+        client.server_update(1.0 / 60.0, h, c);
+        let from_client: Vec<GameEvent> = client.events.iter().filter(|e| e.origin == 1).copied().collect();
+        assert!(from_client.iter().any(|e| e.id < host.next_event_id[0]));
+
+        host.merge_events(&client.to_render().events);
+        for ev in &from_client {
+            assert!(host.events.contains(ev));
+        }
+        let held = host.events.len();
+        host.merge_events(&client.to_render().events);
+        assert_eq!(host.events.len(), held);
+    }
+
+    #[test]
+    fn test_hard_wall_hit_flashes_and_soft_one_does_not() {
+        let mut gs = GameState::new();
+        let (h, c) = idle(&gs);
+        gs.countdown = 0.0;
+        let pr = gs.geometry.puck_radius;
+        gs.puck = Puck::new(pr + 2.0, 300.0, -100.0, 0.0);
+        gs.server_update(1.0 / 60.0, h, c);
+        assert_eq!(gs.wall_flash, 0.0);
+        assert!(!gs.events.iter().any(|e| matches!(e.kind, GameEventKind::WallHit { .. })));
+
+        gs.puck = Puck::new(pr + 2.0, 300.0, -800.0, 0.0);
+        gs.server_update(1.0 / 60.0, h, c);
+        assert!(gs.wall_flash > 0.0);
+        assert!(gs.events.iter().any(|e| matches!(e.kind, GameEventKind::WallHit { side: WallSide::Left, speed } if speed >= audio::WALL_HIT_SPEED_THRESHOLD)));
+    }
+}
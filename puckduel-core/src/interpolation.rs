@@ -0,0 +1,271 @@
+use std::collections::VecDeque;
+use crate::config::interpolation::*;
+use crate::config::{DEAD_RECKONING_BLEND, DEAD_RECKONING_VELOCITY_BLEND};
+use crate::game::RenderState;
+use crate::physics::Puck;
+
+/// Remote states keyed by local arrival time (seconds). They are rendered a
+/// little in the past, so there is nearly always a pair to interpolate between.
+pub struct SnapshotBuffer {
+    snapshots:    VecDeque<(f64, RenderState)>,
+    last_arrival: Option<f64>,
+    /// Smoothed gap between arrivals and its mean deviation, in seconds.
+    interval:     f32,
+    jitter:       f32,
+}
+
+impl Default for SnapshotBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SnapshotBuffer {
+    pub fn new() -> Self {
+        Self {
+            snapshots:    VecDeque::with_capacity(SNAPSHOT_BUFFER_LEN),
+            last_arrival: None,
+            interval:     1.0 / 60.0,
+            jitter:       0.0,
+        }
+    }
+
+    pub fn push(&mut self, now: f64, state: RenderState) {
+        if let Some(last) = self.last_arrival {
+            let gap = (now - last) as f32;
+            self.jitter += ((gap - self.interval).abs() - self.jitter) * 0.1;
+            self.interval += (gap - self.interval) * 0.1;
+        }
+        self.last_arrival = Some(now);
+        self.snapshots.push_back((now, state));
+        if self.snapshots.len() > SNAPSHOT_BUFFER_LEN {
+            self.snapshots.pop_front();
+        }
+    }
+
+    /// Forget buffered states, e.g. once this peer takes over authority.
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+        self.last_arrival = None;
+    }
+
+    /// How far behind real time states are rendered: one arrival interval plus
+    /// twice the jitter, so the delay grows on bad networks and shrinks on good ones.
+    pub fn delay(&self) -> f32 {
+        (self.interval + 2.0 * self.jitter).clamp(MIN_RENDER_DELAY, MAX_RENDER_DELAY)
+    }
+
+    /// True once rendering has caught up with the newest snapshot, i.e. there
+    /// is nothing left to interpolate towards.
+    pub fn is_dry(&self, now: f64) -> bool {
+        self.snapshots.back().is_none_or(|(at, _)| *at < now - self.delay() as f64)
+    }
+
+    /// The remote state as of `now - delay()`, interpolated between the two
+    /// snapshots around that time. Holds the newest one when the buffer runs dry.
+    pub fn sample(&self, now: f64) -> Option<RenderState> {
+        let t = now - self.delay() as f64;
+        match self.snapshots.iter().position(|(at, _)| *at >= t) {
+            None => self.snapshots.back().map(|(_, s)| s.clone()),
+            Some(0) => self.snapshots.front().map(|(_, s)| s.clone()),
+            Some(i) => {
+                let (ta, a) = &self.snapshots[i - 1];
+                let (tb, b) = &self.snapshots[i];
+                let f = ((t - ta) / (tb - ta).max(1e-6)) as f32;
+                Some(lerp_state(a, b, f))
+            }
+        }
+    }
+}
+
+fn lerp(a: f32, b: f32, f: f32) -> f32 {
+    a + (b - a) * f
+}
+
+fn lerp2(a: [f32; 2], b: [f32; 2], f: f32) -> [f32; 2] {
+    [lerp(a[0], b[0], f), lerp(a[1], b[1], f)]
+}
+
+fn distance(a: [f32; 2], b: [f32; 2]) -> f32 {
+    (a[0] - b[0]).hypot(a[1] - b[1])
+}
+
+/// Continuous fields blended by `f`; everything else comes from `b`. A puck
+/// that jumped (a goal reset) is not swept across the table.
+fn lerp_state(a: &RenderState, b: &RenderState, f: f32) -> RenderState {
+    let mut out = b.clone();
+    if distance(a.puck, b.puck) > DEAD_RECKONING_SNAP_THRESHOLD {
+        return out;
+    }
+    out.puck = lerp2(a.puck, b.puck, f);
+    out.puck_vx = lerp(a.puck_vx, b.puck_vx, f);
+    out.puck_vy = lerp(a.puck_vy, b.puck_vy, f);
+    out.puck_speed = lerp(a.puck_speed, b.puck_speed, f);
+    out.host_paddle = lerp2(a.host_paddle, b.host_paddle, f);
+    out.client_paddle = lerp2(a.client_paddle, b.client_paddle, f);
+    out
+}
+
+/// Pulls a dead-reckoned puck towards an authoritative `state`, snapping
+/// instead when the two are further apart than `DEAD_RECKONING_SNAP_THRESHOLD`.
+pub fn reconcile_puck(puck: &mut Puck, state: &RenderState) {
+    if distance([puck.x, puck.y], state.puck) > DEAD_RECKONING_SNAP_THRESHOLD {
+        *puck = Puck::new(state.puck[0], state.puck[1], state.puck_vx, state.puck_vy);
+        return;
+    }
+    puck.x = lerp(puck.x, state.puck[0], DEAD_RECKONING_BLEND);
+    puck.y = lerp(puck.y, state.puck[1], DEAD_RECKONING_BLEND);
+    puck.vx = lerp(puck.vx, state.puck_vx, DEAD_RECKONING_VELOCITY_BLEND);
+    puck.vy = lerp(puck.vy, state.puck_vy, DEAD_RECKONING_VELOCITY_BLEND);
+}
+
+/// Eases what is shown towards the state being rendered: adaptively while the
+/// opponent is authoritative, and with `HANDOFF_BLEND` just after authority
+/// changes hands so the puck doesn't jump.
+#[derive(Default)]
+pub struct StateSmoother {
+    shown:   Option<RenderState>,
+    handoff: bool,
+}
+
+impl StateSmoother {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Called when authority changes hands; the next states are blended in.
+    pub fn start_handoff(&mut self) {
+        self.handoff = true;
+    }
+
+    /// Blend factor for a puck `error` pixels off: `PUCK_POSITION_LERP` at half
+    /// the adaptive threshold, more for larger errors, within MIN/MAX_BLEND.
+    fn puck_blend(error: f32) -> f32 {
+        (PUCK_POSITION_LERP * (0.5 + error / ADAPTIVE_ERROR_THRESHOLD)).clamp(MIN_BLEND, MAX_BLEND)
+    }
+
+    /// Smooths a state rendered from the opponent's snapshots. `opponent` is the
+    /// player index (0 = host) whose paddle we only see remotely.
+    pub fn remote(&mut self, target: RenderState, opponent: usize) -> RenderState {
+        let Some(prev) = self.shown.as_ref() else {
+            return self.show(target);
+        };
+        let error = distance(prev.puck, target.puck);
+        if error > DEAD_RECKONING_SNAP_THRESHOLD {
+            self.handoff = false;
+            return self.show(target);
+        }
+        let blend = if self.handoff { HANDOFF_BLEND } else { Self::puck_blend(error) };
+        let mut out = target;
+        out.puck = lerp2(prev.puck, out.puck, blend);
+        out.puck_vx = lerp(prev.puck_vx, out.puck_vx, PUCK_VELOCITY_LERP);
+        out.puck_vy = lerp(prev.puck_vy, out.puck_vy, PUCK_VELOCITY_LERP);
+        if opponent == 0 {
+            out.host_paddle = lerp2(prev.host_paddle, out.host_paddle, OPPONENT_PADDLE_LERP);
+        } else {
+            out.client_paddle = lerp2(prev.client_paddle, out.client_paddle, OPPONENT_PADDLE_LERP);
+        }
+        self.handoff &= error > 1.0;
+        self.show(out)
+    }
+
+    /// Passes a locally simulated state through, blending the puck in only
+    /// while a handoff is in progress.
+    pub fn local(&mut self, state: RenderState) -> RenderState {
+        match self.shown.as_ref() {
+            Some(prev) if self.handoff => {
+                let error = distance(prev.puck, state.puck);
+                if !(1.0..=DEAD_RECKONING_SNAP_THRESHOLD).contains(&error) {
+                    self.handoff = false;
+                    return self.show(state);
+                }
+                let mut out = state;
+                out.puck = lerp2(prev.puck, out.puck, HANDOFF_BLEND);
+                self.show(out)
+            }
+            _ => self.show(state),
+        }
+    }
+
+    fn show(&mut self, state: RenderState) -> RenderState {
+        self.shown = Some(state.clone());
+        state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::GameState;
+
+    fn state_at(x: f32, y: f32) -> RenderState {
+        let mut gs = GameState::new();
+        gs.puck.x = x;
+        gs.puck.y = y;
+        gs.to_render()
+    }
+
+    #[test]
+    fn test_sample_interpolates_between_snapshots() {
+        let mut buf = SnapshotBuffer::new();
+        let step = 1.0 / 60.0;
+        for i in 0..10 {
+            buf.push(i as f64 * step, state_at(100.0 + i as f32 * 6.0, 300.0));
+        }
+        let now = 9.0 * step;
+        let t = now - buf.delay() as f64;
+        let expected = 100.0 + (t / step) as f32 * 6.0;
+        let s = buf.sample(now).unwrap();
+        assert!((s.puck[0] - expected).abs() < 0.01, "{} vs {}", s.puck[0], expected);
+    }
+
+    #[test]
+    fn test_delay_grows_with_jitter() {
+        let mut steady = SnapshotBuffer::new();
+        let mut jittery = SnapshotBuffer::new();
+        let (mut t, mut u) = (0.0, 0.0);
+        for i in 0..60 {
+            steady.push(t, state_at(180.0, 320.0));
+            jittery.push(u, state_at(180.0, 320.0));
+            t += 1.0 / 60.0;
+            u += if i % 2 == 0 { 0.002 } else { 0.031 };
+        }
+        assert!(jittery.delay() > steady.delay());
+        assert!(steady.delay() >= MIN_RENDER_DELAY && jittery.delay() <= MAX_RENDER_DELAY);
+    }
+
+    #[test]
+    fn test_no_sweep_across_goal_reset() {
+        let mut buf = SnapshotBuffer::new();
+        buf.push(0.0, state_at(180.0, 20.0));
+        buf.push(0.1, state_at(180.0, 320.0));
+        let s = buf.sample(0.05 + buf.delay() as f64).unwrap();
+        assert_eq!(s.puck, [180.0, 320.0]);
+    }
+
+    #[test]
+    fn test_reconcile_blends_small_errors_and_snaps_large_ones() {
+        let mut puck = Puck::new(100.0, 300.0, 0.0, 0.0);
+        let mut target = state_at(110.0, 300.0);
+        target.puck_vx = 100.0;
+        reconcile_puck(&mut puck, &target);
+        assert!(puck.x > 100.0 && puck.x < 110.0);
+        assert!(puck.vx > 0.0 && puck.vx < 100.0);
+
+        reconcile_puck(&mut puck, &state_at(300.0, 100.0));
+        assert_eq!((puck.x, puck.y), (300.0, 100.0));
+    }
+
+    #[test]
+    fn test_handoff_blends_then_settles() {
+        let mut sm = StateSmoother::new();
+        sm.remote(state_at(180.0, 300.0), 1);
+        sm.start_handoff();
+        let first = sm.local(state_at(180.0, 340.0));
+        assert!(first.puck[1] > 300.0 && first.puck[1] < 340.0);
+        for _ in 0..20 {
+            sm.local(state_at(180.0, 340.0));
+        }
+        assert_eq!(sm.local(state_at(180.0, 341.0)).puck, [180.0, 341.0]);
+    }
+}
@@ -1,3 +1,13 @@
 pub mod config;
 pub mod physics;
 pub mod game;
+pub mod rules;
+pub mod events;
+pub mod ai;
+pub mod protocol;
+pub mod interpolation;
+pub mod rollback;
+pub mod netstats;
+pub mod traversal;
+pub mod auth;
+pub mod crypto;
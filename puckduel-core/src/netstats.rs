@@ -0,0 +1,202 @@
+use std::collections::VecDeque;
+use serde::{Serialize, Deserialize};
+use crate::config::network::{HEARTBEAT_TIMEOUT_MS, PING_LOSS_WINDOW, PING_TIMEOUT_MS, RECONNECT_GRACE_SECS};
+
+/// Which transport game traffic is currently flowing over.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq, Default)]
+pub enum NetPath {
+    /// Direct peer-to-peer after a successful hole punch.
+    P2p,
+    /// Through the game server's relay.
+    #[default]
+    Relay,
+    /// The game server runs the match (authoritative and solo rooms).
+    Server,
+}
+
+/// Connection quality for the UI. Times are milliseconds; `loss` is the
+/// fraction of recent pings left unanswered.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Default)]
+pub struct NetStats {
+    pub path:       NetPath,
+    pub rtt_ms:     f32,
+    pub jitter_ms:  f32,
+    pub loss:       f32,
+    /// Share of the opponent's (or server's) state packets that never arrived.
+    pub state_loss: f32,
+    /// While the other end is silent, seconds left before the match is forfeited.
+    pub forfeit_in: Option<f32>,
+}
+
+/// Round-trip bookkeeping for one transport. RTT and jitter are smoothed like
+/// TCP's SRTT/RTTVAR (RFC 6298); loss is taken over the last `PING_LOSS_WINDOW` pings.
+#[derive(Debug, Default)]
+pub struct PingTracker {
+    next_id:     u32,
+    /// Pings in flight as (id, send time in seconds).
+    outstanding: VecDeque<(u32, f64)>,
+    /// Outcome of recent pings, newest last; `true` when answered.
+    results:     VecDeque<bool>,
+    srtt:        Option<f32>,
+    rttvar:      f32,
+}
+
+impl PingTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a ping sent at `now` and returns the id to put in its header.
+    pub fn send(&mut self, now: f64) -> u32 {
+        self.expire(now);
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        self.outstanding.push_back((id, now));
+        id
+    }
+
+    /// Handles the pong for ping `id`; unknown or expired ids are ignored.
+    pub fn receive(&mut self, id: u32, now: f64) {
+        let Some(i) = self.outstanding.iter().position(|&(p, _)| p == id) else {
+            return;
+        };
+        let (_, sent) = self.outstanding.remove(i).unwrap();
+        let rtt = ((now - sent) * 1000.0) as f32;
+        match self.srtt {
+            None => {
+                self.srtt = Some(rtt);
+                self.rttvar = rtt / 2.0;
+            }
+            Some(srtt) => {
+                self.rttvar += ((srtt - rtt).abs() - self.rttvar) * 0.25;
+                self.srtt = Some(srtt + (rtt - srtt) * 0.125);
+            }
+        }
+        self.record(true);
+    }
+
+    /// Counts pings older than `PING_TIMEOUT_MS` as lost.
+    fn expire(&mut self, now: f64) {
+        let timeout = PING_TIMEOUT_MS as f64 / 1000.0;
+        while self.outstanding.front().is_some_and(|&(_, sent)| now - sent > timeout) {
+            self.outstanding.pop_front();
+            self.record(false);
+        }
+    }
+
+    fn record(&mut self, answered: bool) {
+        self.results.push_back(answered);
+        if self.results.len() > PING_LOSS_WINDOW {
+            self.results.pop_front();
+        }
+    }
+
+    pub fn rtt_ms(&self) -> f32 {
+        self.srtt.unwrap_or(0.0)
+    }
+
+    pub fn jitter_ms(&self) -> f32 {
+        self.rttvar
+    }
+
+    pub fn loss(&self) -> f32 {
+        if self.results.is_empty() {
+            return 0.0;
+        }
+        self.results.iter().filter(|&&ok| !ok).count() as f32 / self.results.len() as f32
+    }
+
+    pub fn stats(&self, path: NetPath) -> NetStats {
+        NetStats {
+            path,
+            rtt_ms: self.rtt_ms(),
+            jitter_ms: self.jitter_ms(),
+            loss: self.loss(),
+            state_loss: 0.0,
+            forfeit_in: None,
+        }
+    }
+}
+
+/// Liveness of the other end, judged by when anything was last heard from it.
+/// Times are seconds on the caller's clock.
+#[derive(Debug)]
+pub struct Heartbeat {
+    last_heard: f64,
+}
+
+impl Heartbeat {
+    pub fn new(now: f64) -> Self {
+        Self { last_heard: now }
+    }
+
+    pub fn heard(&mut self, now: f64) {
+        self.last_heard = now;
+    }
+
+    pub fn silent_for(&self, now: f64) -> f64 {
+        now - self.last_heard
+    }
+
+    /// Silent for longer than `HEARTBEAT_TIMEOUT_MS`: treat the other end as gone.
+    pub fn is_away(&self, now: f64) -> bool {
+        self.silent_for(now) > HEARTBEAT_TIMEOUT_MS as f64 / 1000.0
+    }
+
+    /// Seconds left in the reconnect grace period while away, else `None`.
+    pub fn forfeit_in(&self, now: f64) -> Option<f32> {
+        self.is_away(now)
+            .then(|| (RECONNECT_GRACE_SECS as f64 - self.silent_for(now)).max(0.0) as f32)
+    }
+
+    /// The grace period ran out without hearing anything.
+    pub fn expired(&self, now: f64) -> bool {
+        self.silent_for(now) > RECONNECT_GRACE_SECS as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rtt_and_jitter() {
+        let mut t = PingTracker::new();
+        let a = t.send(0.0);
+        t.receive(a, 0.100);
+        assert_eq!(t.rtt_ms(), 100.0);
+
+        let b = t.send(0.5);
+        t.receive(b, 0.540);
+        assert!(t.rtt_ms() < 100.0 && t.rtt_ms() > 40.0);
+        assert!(t.jitter_ms() > 0.0);
+        assert_eq!(t.loss(), 0.0);
+    }
+
+    #[test]
+    fn test_unanswered_pings_count_as_lost() {
+        let mut t = PingTracker::new();
+        let a = t.send(0.0);
+        t.send(0.5);
+        t.receive(a, 0.05);
+        // The second ping times out once a later one is sent
+        t.send(3.0);
+        assert_eq!(t.loss(), 0.5);
+        // A pong after its ping expired is ignored
+        t.receive(1, 3.1);
+        assert_eq!(t.loss(), 0.5);
+    }
+
+    #[test]
+    fn test_heartbeat_grace() {
+        let grace = RECONNECT_GRACE_SECS as f64;
+        let mut hb = Heartbeat::new(0.0);
+        assert!(!hb.is_away(1.0));
+        assert_eq!(hb.forfeit_in(1.0), None);
+        assert_eq!(hb.forfeit_in(grace - 5.0), Some(5.0));
+        assert!(!hb.expired(grace - 5.0));
+        assert!(hb.expired(grace + 0.1));
+        hb.heard(grace);
+        assert!(!hb.is_away(grace + 0.5));
+    }
+}
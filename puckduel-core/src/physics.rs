@@ -1,4 +1,5 @@
-use crate::config::*;
+use crate::config::{TableGeometry, PhysicsParams};
+use crate::events::WallSide;
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Puck {
@@ -16,6 +17,31 @@ pub struct Paddle {
     pub pvy: f32,
 }
 
+/// Integration step used by [`predict_trajectory`]; matches the 4 × 60 Hz
+/// substeps of `GameState::server_update`.
+pub const TRAJECTORY_STEP: f32 = 1.0 / 240.0;
+
+/// One sample of a predicted puck path, `t` seconds from now.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TrajectoryPoint {
+    pub t:  f32,
+    pub x:  f32,
+    pub y:  f32,
+    pub vx: f32,
+    pub vy: f32,
+}
+
+/// A collision reported by [`sweep_puck`]. Speeds are the puck's approach speed
+/// along the contact normal.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Contact {
+    /// `player` is 0 for the host paddle, 1 for the client paddle; `impulse` is the
+    /// change in the puck's normal velocity.
+    Paddle { player: usize, impulse: f32, point: [f32; 2] },
+    Wall { side: WallSide, speed: f32 },
+    Post { speed: f32 },
+}
+
 impl Puck {
     pub fn new(x: f32, y: f32, vx: f32, vy: f32) -> Self {
         Self { x, y, vx, vy }
@@ -32,113 +58,403 @@ impl Paddle {
     }
 }
 
-pub fn collide_paddle_puck(puck: &mut Puck, pad: &Paddle) -> bool {
+/// Maximum number of time-of-impact events resolved per swept substep.
+const MAX_SWEEP_ITERATIONS: usize = 4;
+
+pub fn collide_paddle_puck(puck: &mut Puck, pad: &Paddle, geo: &TableGeometry, params: &PhysicsParams) -> bool {
     let dx = puck.x - pad.x;
     let dy = puck.y - pad.y;
     let d = (dx * dx + dy * dy).sqrt();
-    let md = PUCK_RADIUS + PADDLE_RADIUS;
+    let md = geo.puck_radius + geo.paddle_radius;
 
     if d >= md || d < 0.001 {
         return false;
     }
 
-    let (nx, ny) = (dx / d, dy / d);
-    puck.x = pad.x + nx * (md + 1.0);
-    puck.y = pad.y + ny * (md + 1.0);
+    resolve_paddle_contact(puck, (pad.x, pad.y), pad, (dx / d, dy / d), geo, params).is_some()
+}
+
+fn resolve_paddle_contact(
+    puck: &mut Puck,
+    (pad_x, pad_y): (f32, f32),
+    pad: &Paddle,
+    (nx, ny): (f32, f32),
+    geo: &TableGeometry,
+    params: &PhysicsParams,
+) -> Option<(f32, [f32; 2])> {
+    let md = geo.puck_radius + geo.paddle_radius;
+    puck.x = pad_x + nx * (md + 1.0);
+    puck.y = pad_y + ny * (md + 1.0);
 
     let rel_vx = puck.vx - pad.pvx;
     let rel_vy = puck.vy - pad.pvy;
     let dot = rel_vx * nx + rel_vy * ny;
 
     if dot < 0.0 {
-        puck.vx -= dot * nx * (1.0 + PADDLE_POWER);
-        puck.vy -= dot * ny * (1.0 + PADDLE_POWER);
+        puck.vx -= dot * nx * (1.0 + params.paddle_power);
+        puck.vy -= dot * ny * (1.0 + params.paddle_power);
 
         let tan_vel = rel_vx * (-ny) + rel_vy * nx;
-        puck.vx -= tan_vel * PADDLE_SURFACE_FRICTION * (-ny);
-        puck.vy -= tan_vel * PADDLE_SURFACE_FRICTION * nx;
+        puck.vx -= tan_vel * params.paddle_surface_friction * (-ny);
+        puck.vy -= tan_vel * params.paddle_surface_friction * nx;
 
-        true
+        let point = [pad_x + nx * geo.paddle_radius, pad_y + ny * geo.paddle_radius];
+        Some((-dot * (1.0 + params.paddle_power), point))
     } else {
-        false
+        None
     }
 }
 
-pub fn collide_corner_puck(puck: &mut Puck, cx: f32, cy: f32, in_zone: bool) -> bool {
+pub fn collide_corner_puck(puck: &mut Puck, cx: f32, cy: f32, in_zone: bool, geo: &TableGeometry, params: &PhysicsParams) -> bool {
+    overlap_corner(puck, cx, cy, in_zone, geo, params).is_some()
+}
+
+fn overlap_corner(puck: &mut Puck, cx: f32, cy: f32, in_zone: bool, geo: &TableGeometry, params: &PhysicsParams) -> Option<f32> {
     if !in_zone {
-        return false;
+        return None;
     }
 
     let dx = puck.x - cx;
     let dy = puck.y - cy;
     let d = (dx * dx + dy * dy).sqrt();
-    let max_d = (CORNER_RADIUS - 2.0) - PUCK_RADIUS;
+    let max_d = geo.corner_reach();
 
     if d <= max_d || d < 0.001 {
-        return false;
+        return None;
     }
 
-    let (nx, ny) = (dx / d, dy / d);
+    resolve_corner_contact(puck, cx, cy, dx / d, dy / d, geo, params)
+}
+
+fn resolve_corner_contact(puck: &mut Puck, cx: f32, cy: f32, nx: f32, ny: f32, geo: &TableGeometry, params: &PhysicsParams) -> Option<f32> {
+    let max_d = geo.corner_reach();
     puck.x = cx + nx * max_d;
     puck.y = cy + ny * max_d;
 
     let dot = puck.vx * nx + puck.vy * ny;
     if dot > 0.0 {
-        puck.vx -= dot * (1.0 + WALL_REST) * nx;
-        puck.vy -= dot * (1.0 + WALL_REST) * ny;
-        true
+        puck.vx -= dot * (1.0 + params.wall_restitution) * nx;
+        puck.vy -= dot * (1.0 + params.wall_restitution) * ny;
+        Some(dot)
     } else {
-        false
+        None
     }
 }
 
-pub fn collide_goal_post(puck: &mut Puck, px: f32, py: f32) -> bool {
+pub fn collide_goal_post(puck: &mut Puck, px: f32, py: f32, geo: &TableGeometry, params: &PhysicsParams) -> bool {
+    overlap_post(puck, px, py, geo, params).is_some()
+}
+
+fn overlap_post(puck: &mut Puck, px: f32, py: f32, geo: &TableGeometry, params: &PhysicsParams) -> Option<f32> {
     let dx = puck.x - px;
     let dy = puck.y - py;
     let d = (dx * dx + dy * dy).sqrt();
-    let min_d = PUCK_RADIUS + GOAL_POST_RADIUS;
+    let min_d = geo.puck_radius + geo.post_radius;
 
     if d >= min_d || d < 0.001 {
-        return false;
+        return None;
     }
 
-    let (nx, ny) = (dx / d, dy / d);
+    resolve_post_contact(puck, px, py, dx / d, dy / d, geo, params)
+}
+
+fn resolve_post_contact(puck: &mut Puck, px: f32, py: f32, nx: f32, ny: f32, geo: &TableGeometry, params: &PhysicsParams) -> Option<f32> {
+    let min_d = geo.puck_radius + geo.post_radius;
     puck.x = px + nx * min_d;
     puck.y = py + ny * min_d;
 
     let dot = puck.vx * nx + puck.vy * ny;
     if dot < 0.0 {
-        puck.vx -= (1.0 + WALL_REST) * dot * nx;
-        puck.vy -= (1.0 + WALL_REST) * dot * ny;
-        true
+        puck.vx -= (1.0 + params.wall_restitution) * dot * nx;
+        puck.vy -= (1.0 + params.wall_restitution) * dot * ny;
+        Some(-dot)
     } else {
-        false
+        None
     }
 }
 
-pub fn apply_friction(puck: &mut Puck, dt: f32) {
+/// Earliest fraction of the displacement `(dx, dy)` at which a point starting at
+/// `(px, py)` reaches distance `r` from `(cx, cy)`, approaching from outside.
+pub fn time_of_impact(px: f32, py: f32, dx: f32, dy: f32, cx: f32, cy: f32, r: f32) -> Option<f32> {
+    let (fx, fy) = (px - cx, py - cy);
+    let a = dx * dx + dy * dy;
+    let b = fx * dx + fy * dy;
+    let c = fx * fx + fy * fy - r * r;
+    // Already overlapping (left to the overlap pass) or moving apart.
+    if a < 1e-9 || b >= 0.0 || c < 0.0 {
+        return None;
+    }
+    let disc = b * b - a * c;
+    if disc < 0.0 {
+        return None;
+    }
+    let t = (-b - disc.sqrt()) / a;
+    (t <= 1.0).then_some(t.max(0.0))
+}
+
+/// Fraction of `(dx, dy)` at which a point inside the circle of radius `r`
+/// around `(cx, cy)` leaves it.
+fn time_of_exit(px: f32, py: f32, dx: f32, dy: f32, cx: f32, cy: f32, r: f32) -> Option<f32> {
+    let (fx, fy) = (px - cx, py - cy);
+    let a = dx * dx + dy * dy;
+    let b = fx * dx + fy * dy;
+    let c = fx * fx + fy * fy - r * r;
+    if a < 1e-9 || c > 0.0 {
+        return None;
+    }
+    let t = (-b + (b * b - a * c).max(0.0).sqrt()) / a;
+    (t <= 1.0).then_some(t.max(0.0))
+}
+
+#[derive(Clone, Copy)]
+enum Collider {
+    Paddle(usize),
+    Post(f32, f32),
+    Corner(f32, f32),
+    SideWall(f32),
+    EndWall(f32),
+}
+
+/// Advances the puck by `dt` using swept (time-of-impact) collision against the
+/// paddles, corner arcs, goal posts and walls, so fast shots cannot tunnel through
+/// a collider within one step. `paddles` hold their end-of-step positions and move
+/// linearly along `pvx`/`pvy` during the step. Returns every contact in order.
+pub fn sweep_puck(puck: &mut Puck, paddles: &[&Paddle], dt: f32, geo: &TableGeometry, params: &PhysicsParams) -> Vec<Contact> {
+    let (pr, tw, th) = (geo.puck_radius, geo.width, geo.height);
+    let rest = params.wall_restitution;
+    let mut contacts = Vec::new();
+    let mut remaining = dt;
+
+    for _ in 0..MAX_SWEEP_ITERATIONS {
+        if remaining <= 0.0 {
+            break;
+        }
+        let (dx, dy) = (puck.vx * remaining, puck.vy * remaining);
+        let mut earliest: Option<(f32, Collider)> = None;
+        let mut consider = |t: Option<f32>, c: Collider| {
+            if let Some(t) = t {
+                if earliest.is_none_or(|(best, _)| t < best) {
+                    earliest = Some((t, c));
+                }
+            }
+        };
+
+        for (i, pad) in paddles.iter().enumerate() {
+            // Sweep in the paddle's frame so paddle motion counts towards the approach.
+            let (sx, sy) = (pad.x - pad.pvx * remaining, pad.y - pad.pvy * remaining);
+            let (rdx, rdy) = (dx - pad.pvx * remaining, dy - pad.pvy * remaining);
+            consider(time_of_impact(puck.x, puck.y, rdx, rdy, sx, sy, pr + geo.paddle_radius), Collider::Paddle(i));
+        }
+        for (px, py) in geo.goal_posts() {
+            consider(time_of_impact(puck.x, puck.y, dx, dy, px, py, pr + geo.post_radius), Collider::Post(px, py));
+        }
+        for (cx, cy) in geo.corner_centres() {
+            if geo.in_corner_zone(puck.x, puck.y, cx, cy) {
+                let t = time_of_exit(puck.x, puck.y, dx, dy, cx, cy, geo.corner_reach())
+                    .filter(|&t| geo.in_corner_zone(puck.x + dx * t, puck.y + dy * t, cx, cy));
+                consider(t, Collider::Corner(cx, cy));
+            }
+        }
+        if dx < 0.0 && puck.x - pr >= 0.0 {
+            consider(Some((pr - puck.x) / dx).filter(|&t| t <= 1.0), Collider::SideWall(pr));
+        } else if dx > 0.0 && puck.x + pr <= tw {
+            consider(Some((tw - pr - puck.x) / dx).filter(|&t| t <= 1.0), Collider::SideWall(tw - pr));
+        }
+        let end_wall = if dy < 0.0 && puck.y - pr >= 0.0 {
+            Some(pr)
+        } else if dy > 0.0 && puck.y + pr <= th {
+            Some(th - pr)
+        } else {
+            None
+        };
+        if let Some(wall_y) = end_wall {
+            let t = Some((wall_y - puck.y) / dy).filter(|&t| t <= 1.0 && !geo.in_goal_gap(puck.x + dx * t));
+            consider(t, Collider::EndWall(wall_y));
+        }
+
+        let Some((t, collider)) = earliest else {
+            puck.x += dx;
+            puck.y += dy;
+            break;
+        };
+        puck.x += dx * t;
+        puck.y += dy * t;
+        remaining *= 1.0 - t;
+
+        match collider {
+            Collider::Paddle(i) => {
+                let pad = paddles[i];
+                let at = (pad.x - pad.pvx * remaining, pad.y - pad.pvy * remaining);
+                let n = normal(puck.x - at.0, puck.y - at.1);
+                if let Some((impulse, point)) = resolve_paddle_contact(puck, at, pad, n, geo, params) {
+                    contacts.push(Contact::Paddle { player: i, impulse, point });
+                }
+            }
+            Collider::Post(px, py) => {
+                let (nx, ny) = normal(puck.x - px, puck.y - py);
+                if let Some(speed) = resolve_post_contact(puck, px, py, nx, ny, geo, params) {
+                    contacts.push(Contact::Post { speed });
+                }
+            }
+            Collider::Corner(cx, cy) => {
+                let (nx, ny) = normal(puck.x - cx, puck.y - cy);
+                if let Some(speed) = resolve_corner_contact(puck, cx, cy, nx, ny, geo, params) {
+                    contacts.push(Contact::Wall { side: WallSide::Corner, speed });
+                }
+            }
+            Collider::SideWall(x) => {
+                let side = if x < tw / 2.0 { WallSide::Left } else { WallSide::Right };
+                contacts.push(Contact::Wall { side, speed: puck.vx.abs() });
+                puck.x = x;
+                puck.vx = -puck.vx * rest;
+            }
+            Collider::EndWall(y) => {
+                let side = if y < th / 2.0 { WallSide::Top } else { WallSide::Bottom };
+                contacts.push(Contact::Wall { side, speed: puck.vy.abs() });
+                puck.y = y;
+                puck.vy = -puck.vy * rest;
+            }
+        }
+    }
+
+    resolve_overlaps(puck, paddles, geo, params, &mut contacts);
+    contacts
+}
+
+/// Overlap-based fallback for contacts a sweep cannot see, e.g. a paddle placed on
+/// top of a resting puck or a puck pushed into a wall by a paddle.
+fn resolve_overlaps(puck: &mut Puck, paddles: &[&Paddle], geo: &TableGeometry, params: &PhysicsParams, contacts: &mut Vec<Contact>) {
+    let (pr, tw, th) = (geo.puck_radius, geo.width, geo.height);
+    let rest = params.wall_restitution;
+
+    let (px, py) = (puck.x, puck.y);
+    for (cx, cy) in geo.corner_centres() {
+        if let Some(speed) = overlap_corner(puck, cx, cy, geo.in_corner_zone(px, py, cx, cy), geo, params) {
+            contacts.push(Contact::Wall { side: WallSide::Corner, speed });
+        }
+    }
+
+    if puck.x - pr < 0.0 {
+        contacts.push(Contact::Wall { side: WallSide::Left, speed: puck.vx.abs() });
+        puck.x = pr;
+        puck.vx = -puck.vx * rest;
+    } else if puck.x + pr > tw {
+        contacts.push(Contact::Wall { side: WallSide::Right, speed: puck.vx.abs() });
+        puck.x = tw - pr;
+        puck.vx = -puck.vx * rest;
+    }
+
+    let in_gap = geo.in_goal_gap(puck.x);
+    if puck.y - pr < 0.0 && !in_gap {
+        contacts.push(Contact::Wall { side: WallSide::Top, speed: puck.vy.abs() });
+        puck.y = pr;
+        puck.vy = -puck.vy * rest;
+    } else if puck.y + pr > th && !in_gap {
+        contacts.push(Contact::Wall { side: WallSide::Bottom, speed: puck.vy.abs() });
+        puck.y = th - pr;
+        puck.vy = -puck.vy * rest;
+    }
+
+    for (px, py) in geo.goal_posts() {
+        if let Some(speed) = overlap_post(puck, px, py, geo, params) {
+            contacts.push(Contact::Post { speed });
+        }
+    }
+
+    for (player, &pad) in paddles.iter().enumerate() {
+        let (dx, dy) = (puck.x - pad.x, puck.y - pad.y);
+        let d = (dx * dx + dy * dy).sqrt();
+        if d >= geo.puck_radius + geo.paddle_radius || d < 0.001 {
+            continue;
+        }
+        if let Some((impulse, point)) = resolve_paddle_contact(puck, (pad.x, pad.y), pad, (dx / d, dy / d), geo, params) {
+            contacts.push(Contact::Paddle { player, impulse, point });
+        }
+    }
+}
+
+/// Advances a puck with no paddles on the table by one `dt` step: friction, walls,
+/// corners and posts exactly as in the live simulation.
+pub fn step_free_puck(puck: &mut Puck, dt: f32, geo: &TableGeometry, params: &PhysicsParams) {
+    apply_friction(puck, dt, params);
+    sweep_puck(puck, &[], dt, geo, params);
+    clamp_max_speed(puck, params);
+}
+
+/// Predicts the free-running puck path for `horizon` seconds with the same friction,
+/// wall, corner and post handling as the live simulation, ignoring paddles. Stops early
+/// once the puck comes to rest or leaves the table through a goal.
+pub fn predict_trajectory(puck: &Puck, geo: &TableGeometry, params: &PhysicsParams, horizon: f32) -> Vec<TrajectoryPoint> {
+    let mut p = puck.clone();
+    let steps = (horizon / TRAJECTORY_STEP).ceil() as usize;
+    let mut points = Vec::with_capacity(steps);
+    for i in 1..=steps {
+        step_free_puck(&mut p, TRAJECTORY_STEP, geo, params);
+        points.push(TrajectoryPoint { t: i as f32 * TRAJECTORY_STEP, x: p.x, y: p.y, vx: p.vx, vy: p.vy });
+        if (p.vx == 0.0 && p.vy == 0.0) || p.y < 0.0 || p.y > geo.height {
+            break;
+        }
+    }
+    points
+}
+
+/// First point where a predicted path crosses the line `y`, interpolated between samples.
+pub fn crossing_at_y(start: &Puck, path: &[TrajectoryPoint], y: f32) -> Option<TrajectoryPoint> {
+    let mut prev = TrajectoryPoint { t: 0.0, x: start.x, y: start.y, vx: start.vx, vy: start.vy };
+    for &pt in path {
+        if (prev.y - y) * (pt.y - y) <= 0.0 && prev.y != pt.y {
+            let f = (y - prev.y) / (pt.y - prev.y);
+            let lerp = |a: f32, b: f32| a + (b - a) * f;
+            return Some(TrajectoryPoint {
+                t:  lerp(prev.t, pt.t),
+                x:  lerp(prev.x, pt.x),
+                y,
+                vx: lerp(prev.vx, pt.vx),
+                vy: lerp(prev.vy, pt.vy),
+            });
+        }
+        prev = pt;
+    }
+    None
+}
+
+fn normal(dx: f32, dy: f32) -> (f32, f32) {
+    let d = (dx * dx + dy * dy).sqrt();
+    if d < 0.001 { (0.0, 1.0) } else { (dx / d, dy / d) }
+}
+
+pub fn apply_friction(puck: &mut Puck, dt: f32, params: &PhysicsParams) {
     let sp = puck.speed();
-    if sp <= MIN_PUCK_SPEED {
+    if sp <= params.min_puck_speed {
         puck.vx = 0.0;
         puck.vy = 0.0;
         return;
     }
-    let loss = (FRICTION * sp * dt).min(sp);
+    let loss = (params.friction * sp * dt).min(sp);
     puck.vx -= puck.vx / sp * loss;
     puck.vy -= puck.vy / sp * loss;
 }
 
-pub fn clamp_max_speed(puck: &mut Puck) {
+pub fn clamp_max_speed(puck: &mut Puck, params: &PhysicsParams) {
     let cs = puck.speed();
-    if cs > MAX_SPEED {
-        puck.vx = puck.vx / cs * MAX_SPEED;
-        puck.vy = puck.vy / cs * MAX_SPEED;
+    if cs > params.max_speed {
+        puck.vx = puck.vx / cs * params.max_speed;
+        puck.vy = puck.vy / cs * params.max_speed;
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::*;
+
+    fn geo() -> TableGeometry {
+        TableGeometry::default()
+    }
+
+    fn params() -> PhysicsParams {
+        PhysicsParams::default()
+    }
 
     #[test]
     fn test_puck_speed() {
@@ -150,7 +466,7 @@ mod tests {
     fn test_collide_paddle_puck_no_collision() {
         let mut puck = Puck::new(100.0, 100.0, 0.0, 0.0);
         let pad = Paddle::new(200.0, 200.0);
-        assert!(!collide_paddle_puck(&mut puck, &pad));
+        assert!(!collide_paddle_puck(&mut puck, &pad, &geo(), &params()));
     }
 
     #[test]
@@ -159,7 +475,7 @@ mod tests {
         let min_dist = PUCK_RADIUS + PADDLE_RADIUS - overlap;
         let mut puck = Puck::new(0.0, min_dist, 0.0, -10.0);
         let pad = Paddle::new(0.0, 0.0);
-        assert!(collide_paddle_puck(&mut puck, &pad));
+        assert!(collide_paddle_puck(&mut puck, &pad, &geo(), &params()));
         assert!(puck.y > min_dist);
     }
 
@@ -167,28 +483,105 @@ mod tests {
     fn test_collide_paddle_puck_reflects_velocity() {
         let mut puck = Puck::new(0.0, PUCK_RADIUS + PADDLE_RADIUS - 1.0, 0.0, -100.0);
         let pad = Paddle::new(0.0, 0.0);
-        collide_paddle_puck(&mut puck, &pad);
+        collide_paddle_puck(&mut puck, &pad, &geo(), &params());
         assert!(puck.vy > 0.0);
     }
 
     #[test]
     fn test_collide_corner_puck_outside_zone() {
         let mut puck = Puck::new(10.0, 10.0, 0.0, 0.0);
-        assert!(!collide_corner_puck(&mut puck, 0.0, 0.0, false));
+        assert!(!collide_corner_puck(&mut puck, 0.0, 0.0, false, &geo(), &params()));
     }
 
     #[test]
     fn test_apply_friction_reduces_speed() {
         let mut puck = Puck::new(0.0, 0.0, 100.0, 0.0);
         let initial_speed = puck.speed();
-        apply_friction(&mut puck, 0.016);
+        apply_friction(&mut puck, 0.016, &params());
         assert!(puck.speed() < initial_speed);
     }
 
     #[test]
     fn test_clamp_max_speed_reduces_velocity() {
         let mut puck = Puck::new(0.0, 0.0, MAX_SPEED + 100.0, 0.0);
-        clamp_max_speed(&mut puck);
+        clamp_max_speed(&mut puck, &params());
         assert!((puck.speed() - MAX_SPEED).abs() < 0.001);
     }
+
+    #[test]
+    fn test_time_of_impact_head_on() {
+        let t = time_of_impact(0.0, 0.0, 100.0, 0.0, 60.0, 0.0, 10.0).unwrap();
+        assert!((t - 0.5).abs() < 0.001);
+        assert!(time_of_impact(0.0, 0.0, -100.0, 0.0, 60.0, 0.0, 10.0).is_none());
+        assert!(time_of_impact(0.0, 0.0, 100.0, 0.0, 60.0, 50.0, 10.0).is_none());
+    }
+
+    #[test]
+    fn test_sweep_puck_does_not_tunnel_through_paddle() {
+        // One substep at max speed would carry the puck clean past the paddle.
+        let pad = Paddle::new(180.0, 300.0);
+        let idle = Paddle::new(-500.0, -500.0);
+        let mut puck = Puck::new(180.0, 400.0, 0.0, -MAX_SPEED);
+        let dt = 0.2;
+        let contacts = sweep_puck(&mut puck, &[&pad, &idle], dt, &geo(), &params());
+        assert_eq!(contacts.len(), 1);
+        assert!(matches!(contacts[0], Contact::Paddle { player: 0, impulse, .. } if impulse > MAX_SPEED));
+        assert!(puck.vy > 0.0);
+        assert!(puck.y > pad.y);
+    }
+
+    #[test]
+    fn test_sweep_puck_catches_fast_paddle_swipe() {
+        let mut pad = Paddle::new(300.0, 320.0);
+        pad.pvx = 200.0 / 0.02;
+        let idle = Paddle::new(-500.0, -500.0);
+        let mut puck = Puck::new(200.0, 320.0, 0.0, 0.0);
+        assert!(!sweep_puck(&mut puck, &[&pad, &idle], 0.02, &geo(), &params()).is_empty());
+        assert!(puck.vx > 0.0);
+    }
+
+    #[test]
+    fn test_sweep_puck_bounces_off_goal_post() {
+        let gx = (TABLE_WIDTH - GOAL_WIDTH) / 2.0;
+        let idle = Paddle::new(-500.0, -500.0);
+        let mut puck = Puck::new(gx, 100.0, 0.0, -MAX_SPEED);
+        let contacts = sweep_puck(&mut puck, &[&idle, &idle], 0.2, &geo(), &params());
+        assert!(matches!(contacts[..], [Contact::Post { speed }] if (speed - MAX_SPEED).abs() < 1.0));
+        assert!(puck.vy > 0.0);
+        assert!(puck.y >= PUCK_RADIUS + GOAL_POST_RADIUS - 0.01);
+    }
+
+    #[test]
+    fn test_sweep_puck_stays_on_table() {
+        let idle = Paddle::new(-500.0, -500.0);
+        let mut puck = Puck::new(100.0, 300.0, -MAX_SPEED, 0.0);
+        let contacts = sweep_puck(&mut puck, &[&idle, &idle], 0.2, &geo(), &params());
+        assert_eq!(contacts, vec![Contact::Wall { side: WallSide::Left, speed: MAX_SPEED }]);
+        assert!(puck.x >= PUCK_RADIUS);
+        assert!(puck.vx > 0.0);
+    }
+
+    #[test]
+    fn test_predict_trajectory_bounces_off_side_wall() {
+        let puck = Puck::new(100.0, 400.0, -600.0, -300.0);
+        let path = predict_trajectory(&puck, &geo(), &params(), 1.0);
+        assert!(path.iter().all(|p| p.x >= PUCK_RADIUS - 0.01));
+        assert!(path.iter().any(|p| p.vx > 0.0));
+        let hit = crossing_at_y(&puck, &path, 120.0).unwrap();
+        assert!(hit.t > 0.0 && hit.t < 1.0);
+        assert!((hit.y - 120.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_predict_trajectory_matches_free_motion_and_stops() {
+        let puck = Puck::new(180.0, 320.0, 0.0, 0.0);
+        assert_eq!(predict_trajectory(&puck, &geo(), &params(), 1.0).len(), 1);
+
+        let puck = Puck::new(180.0, 320.0, 0.0, -200.0);
+        let path = predict_trajectory(&puck, &geo(), &params(), 0.5);
+        let last = path.last().unwrap();
+        assert!((last.t - 0.5).abs() < 0.01);
+        assert!(last.x == 180.0 && last.y < 320.0 - 90.0);
+        assert!(last.vy > -200.0);
+    }
 }
@@ -0,0 +1,320 @@
+use std::fmt;
+use crate::config::network::PROTOCOL_VERSION;
+
+/// First two bytes of every datagram.
+pub const MAGIC: [u8; 2] = *b"PD";
+/// magic (2) + version (4) + message type (1) + sequence (4) + payload length (2).
+pub const HEADER_LEN: usize = 13;
+
+/// What a datagram's payload holds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum MessageType {
+    /// UTF-8 lobby/control text: `CREATE`, `JOIN:<code>`, `START`, `GAME_OVER`, ...
+    Command = 1,
+    /// Pointer position: x and y as little-endian f32.
+    Input = 2,
+    /// A bincode-encoded `RenderState`.
+    State = 3,
+    /// P2P connectivity check; no payload. `seq` is `traversal::PROBE` for a
+    /// probe and `traversal::PROBE_REPLY` for the answer.
+    Hello = 4,
+    /// Latency probe; the reply `Pong` repeats the ping's `seq`.
+    Ping = 5,
+    Pong = 6,
+    /// LAN game advertisement, broadcast by a host; the payload is the room name.
+    Announce = 7,
+    /// X25519 public key opening an encrypted channel; the server answers with
+    /// its own (see `crypto`).
+    Handshake = 8,
+    /// A whole packet encrypted with a `crypto::Cipher`.
+    Sealed = 9,
+}
+
+impl MessageType {
+    fn from_u8(b: u8) -> Option<Self> {
+        match b {
+            1 => Some(Self::Command),
+            2 => Some(Self::Input),
+            3 => Some(Self::State),
+            4 => Some(Self::Hello),
+            5 => Some(Self::Ping),
+            6 => Some(Self::Pong),
+            7 => Some(Self::Announce),
+            8 => Some(Self::Handshake),
+            9 => Some(Self::Sealed),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Header {
+    pub version: u32,
+    pub kind:    MessageType,
+    /// Sender's packet counter; 0 for commands.
+    pub seq:     u32,
+    pub len:     u16,
+}
+
+/// A decoded datagram borrowing its payload from the receive buffer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Packet<'a> {
+    pub header:  Header,
+    pub payload: &'a [u8],
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProtocolError {
+    TooShort,
+    /// Not a framed packet at all, e.g. a datagram from a pre-framing build.
+    BadMagic,
+    VersionMismatch { ours: u32, theirs: u32 },
+    UnknownType(u8),
+    /// The header's length disagrees with the datagram size.
+    BadLength,
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooShort => write!(f, "packet shorter than its header"),
+            Self::BadMagic => write!(f, "not a Puck Duel packet"),
+            Self::VersionMismatch { ours, theirs } => write!(
+                f,
+                "incompatible game version (this app speaks protocol v{}, the other side v{}); please update",
+                ours, theirs
+            ),
+            Self::UnknownType(t) => write!(f, "unknown message type {}", t),
+            Self::BadLength => write!(f, "payload length does not match the header"),
+        }
+    }
+}
+
+impl std::error::Error for ProtocolError {}
+
+/// Frames `payload` with a header for the current `PROTOCOL_VERSION`.
+pub fn encode(kind: MessageType, seq: u32, payload: &[u8]) -> Vec<u8> {
+    let len = u16::try_from(payload.len()).expect("payload larger than a datagram");
+    let mut out = Vec::with_capacity(HEADER_LEN + payload.len());
+    out.extend_from_slice(&MAGIC);
+    out.extend_from_slice(&PROTOCOL_VERSION.to_le_bytes());
+    out.push(kind as u8);
+    out.extend_from_slice(&seq.to_le_bytes());
+    out.extend_from_slice(&len.to_le_bytes());
+    out.extend_from_slice(payload);
+    out
+}
+
+/// Parses and validates a datagram. Packets from another protocol version are
+/// rejected with `VersionMismatch` rather than misread.
+pub fn decode(data: &[u8]) -> Result<Packet<'_>, ProtocolError> {
+    if data.len() < MAGIC.len() || data[..2] != MAGIC {
+        return Err(ProtocolError::BadMagic);
+    }
+    if data.len() < HEADER_LEN {
+        return Err(ProtocolError::TooShort);
+    }
+    let version = u32::from_le_bytes([data[2], data[3], data[4], data[5]]);
+    if version != PROTOCOL_VERSION {
+        return Err(ProtocolError::VersionMismatch { ours: PROTOCOL_VERSION, theirs: version });
+    }
+    let kind = MessageType::from_u8(data[6]).ok_or(ProtocolError::UnknownType(data[6]))?;
+    let seq = u32::from_le_bytes([data[7], data[8], data[9], data[10]]);
+    let len = u16::from_le_bytes([data[11], data[12]]);
+    let payload = data.get(HEADER_LEN..HEADER_LEN + len as usize).ok_or(ProtocolError::BadLength)?;
+    Ok(Packet { header: Header { version, kind, seq, len }, payload })
+}
+
+pub fn encode_command(text: &str) -> Vec<u8> {
+    encode(MessageType::Command, 0, text.as_bytes())
+}
+
+pub fn encode_input(seq: u32, ptr: [f32; 2]) -> Vec<u8> {
+    let mut payload = [0u8; 8];
+    payload[..4].copy_from_slice(&ptr[0].to_le_bytes());
+    payload[4..].copy_from_slice(&ptr[1].to_le_bytes());
+    encode(MessageType::Input, seq, &payload)
+}
+
+/// Pointer carried by an `Input` payload, if it is well-formed and finite.
+pub fn decode_input(payload: &[u8]) -> Option<[f32; 2]> {
+    if payload.len() != 8 {
+        return None;
+    }
+    let x = f32::from_le_bytes([payload[0], payload[1], payload[2], payload[3]]);
+    let y = f32::from_le_bytes([payload[4], payload[5], payload[6], payload[7]]);
+    (x.is_finite() && y.is_finite()).then_some([x, y])
+}
+
+impl Packet<'_> {
+    /// The text of a `Command` packet.
+    pub fn command(&self) -> Option<&str> {
+        if self.header.kind != MessageType::Command {
+            return None;
+        }
+        std::str::from_utf8(self.payload).ok().map(str::trim)
+    }
+
+    /// The room name of an `Announce` packet.
+    pub fn announce(&self) -> Option<&str> {
+        if self.header.kind != MessageType::Announce {
+            return None;
+        }
+        std::str::from_utf8(self.payload).ok().map(str::trim)
+    }
+}
+
+/// How a packet's sequence number relates to what a [`SequenceTracker`] has seen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Arrival {
+    /// Newer than anything before it; the only packets worth applying.
+    Newest,
+    /// Out of order: first copy of an older sequence number.
+    Late,
+    /// Already seen (e.g. the relay copy of a packet that came over P2P), or too
+    /// old to tell.
+    Duplicate,
+}
+
+/// Sequence bookkeeping for one incoming stream (one sender, one message type).
+/// Remembers the last 64 sequence numbers so late packets can be told apart
+/// from duplicates, and counts losses the way RTP receivers do.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SequenceTracker {
+    first:          u32,
+    latest:         Option<u32>,
+    /// Bit `i` set when `latest - i` has been received.
+    window:         u64,
+    /// Distinct sequence numbers received.
+    pub received:   u64,
+    pub late:       u64,
+    pub duplicates: u64,
+}
+
+impl SequenceTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records `seq` and classifies it. Comparisons wrap, so counters may overflow.
+    pub fn record(&mut self, seq: u32) -> Arrival {
+        let Some(latest) = self.latest else {
+            self.first = seq;
+            self.latest = Some(seq);
+            self.window = 1;
+            self.received = 1;
+            return Arrival::Newest;
+        };
+        let ahead = seq.wrapping_sub(latest) as i32;
+        if ahead > 0 {
+            self.window = if ahead < 64 { (self.window << ahead) | 1 } else { 1 };
+            self.latest = Some(seq);
+            self.received += 1;
+            return Arrival::Newest;
+        }
+        let behind = ahead.unsigned_abs();
+        if behind >= 64 || self.window & (1 << behind) != 0 || (seq.wrapping_sub(self.first) as i32) < 0 {
+            self.duplicates += 1;
+            return Arrival::Duplicate;
+        }
+        self.window |= 1 << behind;
+        self.received += 1;
+        self.late += 1;
+        Arrival::Late
+    }
+
+    /// Packets the sender has sent so far, judging by the sequence range seen.
+    pub fn expected(&self) -> u64 {
+        self.latest.map_or(0, |l| l.wrapping_sub(self.first) as u64 + 1)
+    }
+
+    pub fn lost(&self) -> u64 {
+        self.expected().saturating_sub(self.received)
+    }
+
+    /// Fraction of the sender's packets that never arrived, in `[0, 1]`.
+    pub fn loss_ratio(&self) -> f32 {
+        match self.expected() {
+            0 => 0.0,
+            n => self.lost() as f32 / n as f32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let data = encode(MessageType::State, 42, b"payload");
+        let pkt = decode(&data).unwrap();
+        assert_eq!(pkt.header.kind, MessageType::State);
+        assert_eq!(pkt.header.seq, 42);
+        assert_eq!(pkt.payload, b"payload");
+
+        let data = encode_command("JOIN:1234");
+        assert_eq!(decode(&data).unwrap().command(), Some("JOIN:1234"));
+        assert_eq!(decode(&data).unwrap().announce(), None);
+
+        let data = encode(MessageType::Announce, 0, "Sam's phone".as_bytes());
+        assert_eq!(decode(&data).unwrap().announce(), Some("Sam's phone"));
+
+        let data = encode_input(7, [12.5, 300.0]);
+        let pkt = decode(&data).unwrap();
+        assert_eq!(pkt.command(), None);
+        assert_eq!(decode_input(pkt.payload), Some([12.5, 300.0]));
+        assert_eq!(decode_input(&[0; 7]), None);
+        assert_eq!(decode_input(&encode_input(0, [f32::NAN, 0.0])[HEADER_LEN..]), None);
+    }
+
+    #[test]
+    fn test_rejects_malformed() {
+        assert_eq!(decode(b"CREATE"), Err(ProtocolError::BadMagic));
+        assert_eq!(decode(b"PD"), Err(ProtocolError::TooShort));
+
+        let mut data = encode_command("START");
+        data.truncate(data.len() - 1);
+        assert_eq!(decode(&data), Err(ProtocolError::BadLength));
+
+        let mut data = encode_command("START");
+        data[6] = 99;
+        assert_eq!(decode(&data), Err(ProtocolError::UnknownType(99)));
+    }
+
+    #[test]
+    fn test_sequence_tracker() {
+        let mut t = SequenceTracker::new();
+        assert_eq!(t.record(10), Arrival::Newest);
+        assert_eq!(t.record(10), Arrival::Duplicate);
+        assert_eq!(t.record(13), Arrival::Newest);
+        assert_eq!(t.record(12), Arrival::Late);
+        assert_eq!(t.record(12), Arrival::Duplicate);
+        assert_eq!(t.record(9), Arrival::Duplicate);
+        assert_eq!(t.expected(), 4);
+        assert_eq!(t.received, 3);
+        assert_eq!(t.lost(), 1);
+        assert_eq!(t.loss_ratio(), 0.25);
+    }
+
+    #[test]
+    fn test_sequence_tracker_wraps() {
+        let mut t = SequenceTracker::new();
+        assert_eq!(t.record(u32::MAX - 1), Arrival::Newest);
+        assert_eq!(t.record(1), Arrival::Newest);
+        assert_eq!(t.record(u32::MAX), Arrival::Late);
+        assert_eq!(t.record(0), Arrival::Late);
+        assert_eq!(t.lost(), 0);
+    }
+
+    #[test]
+    fn test_version_mismatch() {
+        let mut data = encode_command("CREATE");
+        data[2..6].copy_from_slice(&(PROTOCOL_VERSION + 1).to_le_bytes());
+        assert_eq!(
+            decode(&data),
+            Err(ProtocolError::VersionMismatch { ours: PROTOCOL_VERSION, theirs: PROTOCOL_VERSION + 1 })
+        );
+    }
+}
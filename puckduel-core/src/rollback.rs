@@ -0,0 +1,277 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::mem::{discriminant, Discriminant};
+use crate::config::rollback::*;
+use crate::events::{GameEvent, GameEventKind};
+use crate::game::GameState;
+
+/// One simulated frame: the state it started from and the inputs it used.
+struct SavedFrame {
+    frame:     u32,
+    state:     GameState,
+    local:     [f32; 2],
+    remote:    [f32; 2],
+    /// The remote input is the real one rather than a prediction.
+    confirmed: bool,
+}
+
+/// GGPO-style rollback over `GameState`. The local input is applied the frame
+/// it is made; the remote input is predicted (its last known value is repeated)
+/// until the real one arrives, and if the guess was wrong the session rewinds
+/// to that frame and re-simulates with the corrected input.
+pub struct RollbackSession {
+    /// 0 when the local player is the host (bottom paddle), 1 for the client.
+    local:       usize,
+    /// The frame `state` is about to simulate.
+    frame:       u32,
+    state:       GameState,
+    history:     VecDeque<SavedFrame>,
+    /// Remote inputs that arrived for frames not simulated yet.
+    pending:     BTreeMap<u32, [f32; 2]>,
+    /// Newest confirmed remote input and its frame; the basis for predictions.
+    last_remote: Option<(u32, [f32; 2])>,
+    rewind_to:   Option<u32>,
+    dt:          f32,
+    /// Times a misprediction forced a re-simulation.
+    pub rollbacks: u64,
+}
+
+impl RollbackSession {
+    /// Starts at `frame` from `state`, with the remote paddle where it is now.
+    pub fn new(local: usize, state: GameState, frame: u32, dt: f32) -> Self {
+        Self {
+            local,
+            frame,
+            state,
+            history: VecDeque::with_capacity(MAX_ROLLBACK_FRAMES + 1),
+            pending: BTreeMap::new(),
+            last_remote: None,
+            rewind_to: None,
+            dt,
+            rollbacks: 0,
+        }
+    }
+
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    pub fn state(&self) -> &GameState {
+        &self.state
+    }
+
+    /// The remote input used for a frame with no confirmed one.
+    fn predicted_remote(&self) -> [f32; 2] {
+        match self.last_remote {
+            Some((_, ptr)) => ptr,
+            None => {
+                let paddle = if self.local == 0 { &self.state.client_paddle } else { &self.state.host_paddle };
+                [paddle.x, paddle.y]
+            }
+        }
+    }
+
+    /// Records the remote player's real input for `frame`. Inputs older than
+    /// the rollback window are too late to apply and are dropped.
+    pub fn add_remote_input(&mut self, frame: u32, ptr: [f32; 2]) {
+        if self.last_remote.is_none_or(|(f, _)| frame > f) {
+            self.last_remote = Some((frame, ptr));
+        }
+        if frame >= self.frame {
+            self.pending.insert(frame, ptr);
+            return;
+        }
+        let Some(saved) = self.history.iter_mut().find(|s| s.frame == frame) else {
+            return;
+        };
+        if saved.confirmed {
+            return;
+        }
+        let error = (saved.remote[0] - ptr[0]).hypot(saved.remote[1] - ptr[1]);
+        saved.remote = ptr;
+        saved.confirmed = true;
+        if error > PREDICTION_TOLERANCE {
+            self.rewind_to = Some(self.rewind_to.map_or(frame, |f| f.min(frame)));
+        }
+    }
+
+    fn simulate(state: &mut GameState, local: usize, dt: f32, local_ptr: [f32; 2], remote_ptr: [f32; 2]) {
+        let (host, client) = if local == 0 { (local_ptr, remote_ptr) } else { (remote_ptr, local_ptr) };
+        state.server_update(dt, host, client);
+    }
+
+    /// Rewinds to the earliest mispredicted frame and replays up to the present,
+    /// re-predicting frames that are still unconfirmed from the newest real input.
+    fn resimulate(&mut self, from: u32) {
+        let Some(start) = self.history.iter().position(|s| s.frame == from) else {
+            return;
+        };
+        self.rollbacks += 1;
+        let mut state = self.history[start].state.clone();
+        let mut remote = self.history[start].remote;
+        for i in start..self.history.len() {
+            let saved = &mut self.history[i];
+            if saved.confirmed {
+                remote = saved.remote;
+            } else {
+                saved.remote = remote;
+            }
+            saved.state = state.clone();
+            Self::simulate(&mut state, self.local, self.dt, saved.local, saved.remote);
+        }
+        let base = self.history[start].state.next_event_id[state.event_origin];
+        Self::relabel_replayed_events(&self.state, &mut state, base);
+        self.state = state;
+    }
+
+    /// The replay emits again from the snapshot's ids, `base` on. Events the
+    /// mispredicted frames already sent and the replay produced again (matched
+    /// in order by kind) keep their ids, so the peer skips them; the rest get
+    /// ids past everything sent.
+    fn relabel_replayed_events(sent: &GameState, replayed: &mut GameState, base: u64) {
+        let origin = replayed.event_origin;
+        let is_new = |e: &GameEvent| e.origin == origin && e.id >= base;
+        let sent_ids: Vec<(u64, Discriminant<GameEventKind>)> =
+            sent.events.iter().filter(|e| is_new(e)).map(|e| (e.id, discriminant(&e.kind))).collect();
+        let tail = replayed.events.iter().position(is_new).unwrap_or(replayed.events.len());
+        let (mut matched, mut next) = (0, sent.next_event_id[origin]);
+        for ev in replayed.events[tail..].iter_mut().filter(|e| is_new(e)) {
+            match sent_ids[matched..].iter().position(|&(_, kind)| kind == discriminant(&ev.kind)) {
+                Some(i) => {
+                    ev.id = sent_ids[matched + i].0;
+                    matched += i + 1;
+                }
+                None => {
+                    ev.id = next;
+                    next += 1;
+                }
+            }
+        }
+        replayed.events[tail..].sort_by_key(|e| e.id);
+        replayed.next_event_id[origin] = next;
+    }
+
+    /// Simulates one frame with `local_ptr` and the best known remote input.
+    pub fn advance(&mut self, local_ptr: [f32; 2]) -> &GameState {
+        if let Some(from) = self.rewind_to.take() {
+            self.resimulate(from);
+        }
+
+        let (remote, confirmed) = match self.pending.remove(&self.frame) {
+            Some(ptr) => (ptr, true),
+            None => (self.predicted_remote(), false),
+        };
+        self.history.push_back(SavedFrame {
+            frame: self.frame,
+            state: self.state.clone(),
+            local: local_ptr,
+            remote,
+            confirmed,
+        });
+        if self.history.len() > MAX_ROLLBACK_FRAMES {
+            self.history.pop_front();
+        }
+        Self::simulate(&mut self.state, self.local, self.dt, local_ptr, remote);
+        self.frame += 1;
+        // Anything still pending for past frames can no longer be applied
+        self.pending = self.pending.split_off(&self.frame);
+        &self.state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::Puck;
+
+    const DT: f32 = 1.0 / 60.0;
+
+    /// A loose puck just below the midline that the client paddle sweeps into.
+    fn setup() -> GameState {
+        let mut gs = GameState::new();
+        gs.countdown = 0.0;
+        gs.puck = Puck::new(180.0, 260.0, 0.0, 0.0);
+        gs
+    }
+
+    fn client_path(frame: u32) -> [f32; 2] {
+        [180.0, 120.0 + frame as f32 * 8.0]
+    }
+
+    #[test]
+    fn test_late_input_matches_on_time_simulation() {
+        let host = [180.0, 520.0];
+
+        // Reference: both inputs known every frame
+        let mut reference = setup();
+        for f in 0..30 {
+            reference.server_update(DT, host, client_path(f));
+        }
+
+        // Host-side session receiving the client's inputs four frames late
+        let mut session = RollbackSession::new(0, setup(), 0, DT);
+        for f in 0..34 {
+            if f >= 4 {
+                session.add_remote_input(f - 4, client_path(f - 4));
+            }
+            if f < 30 {
+                session.advance(host);
+            }
+        }
+        session.advance(host);
+        assert!(session.rollbacks > 0);
+
+        let mut expected = reference;
+        expected.server_update(DT, host, client_path(29));
+        assert_eq!(session.state().puck, expected.puck);
+    }
+
+    #[test]
+    fn test_replayed_events_reach_the_peer_once() {
+        let host = [180.0, 520.0];
+        let mut reference = setup();
+        for f in 0..30 {
+            reference.server_update(DT, host, client_path(f));
+        }
+
+        // The peer takes in every state the session sends, rollbacks included
+        let mut session = RollbackSession::new(0, setup(), 0, DT);
+        let mut peer = setup();
+        for f in 0..34 {
+            if f >= 4 {
+                session.add_remote_input(f - 4, client_path(f - 4));
+            }
+            if f < 30 {
+                session.advance(host);
+            }
+            peer.merge_events(&session.state().events);
+        }
+        assert!(session.rollbacks > 0);
+
+        let hits = |gs: &GameState| gs.events.iter().filter(|e| matches!(e.kind, GameEventKind::PaddleHit { .. })).count();
+        assert!(hits(&reference) > 0);
+        assert_eq!(hits(&peer), hits(&reference));
+    }
+
+    #[test]
+    fn test_correct_prediction_does_not_roll_back() {
+        let mut session = RollbackSession::new(1, setup(), 0, DT);
+        let host = [180.0, 520.0];
+        session.add_remote_input(0, host);
+        for f in 0..20 {
+            session.advance([180.0, 120.0]);
+            session.add_remote_input(f + 1, host);
+        }
+        assert_eq!(session.rollbacks, 0);
+    }
+
+    #[test]
+    fn test_inputs_outside_window_are_dropped() {
+        let mut session = RollbackSession::new(0, setup(), 100, DT);
+        for _ in 0..(MAX_ROLLBACK_FRAMES + 5) {
+            session.advance([180.0, 520.0]);
+        }
+        session.add_remote_input(100, [0.0, 0.0]);
+        session.advance([180.0, 520.0]);
+        assert_eq!(session.rollbacks, 0);
+    }
+}
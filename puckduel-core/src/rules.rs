@@ -0,0 +1,191 @@
+use std::fmt;
+use std::str::FromStr;
+use serde::{Serialize, Deserialize};
+use crate::config::WINNING_SCORE;
+
+/// Limits on rules a player may ask the server for.
+pub const MAX_TARGET_SCORE: u32 = 21;
+pub const MAX_BEST_OF: u32 = 9;
+/// Longest game clock, in seconds.
+pub const MAX_TIME_LIMIT: f32 = 1800.0;
+
+/// What happens when a timed game's clock runs out with the score level.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub enum Overtime {
+    /// Play on; the next goal wins the game.
+    SuddenDeath,
+    /// The game ends without a winner.
+    Draw,
+}
+
+/// How a match is won. The defaults reproduce the classic "first to 6" single game.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+pub struct MatchRules {
+    /// Goals needed to win a game.
+    pub target_score: u32,
+    /// Require a two-goal lead once the target is reached.
+    pub win_by_two:   bool,
+    /// Length of each game in seconds of play; `None` for untimed games.
+    pub time_limit:   Option<f32>,
+    pub overtime:     Overtime,
+    /// Number of games in the series; the first to win a majority takes the match.
+    pub best_of:      u32,
+}
+
+/// Outcome of a timed game when its clock expires.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeUp {
+    Winner(usize),
+    SuddenDeath,
+    Draw,
+}
+
+impl Default for MatchRules {
+    fn default() -> Self {
+        Self {
+            target_score: WINNING_SCORE,
+            win_by_two:   false,
+            time_limit:   None,
+            overtime:     Overtime::SuddenDeath,
+            best_of:      1,
+        }
+    }
+}
+
+impl MatchRules {
+    /// Winner of the current game for `score`, if it is decided.
+    pub fn game_winner(&self, score: [u32; 2], sudden_death: bool) -> Option<usize> {
+        let leader = match score[0].cmp(&score[1]) {
+            std::cmp::Ordering::Greater => 0,
+            std::cmp::Ordering::Less => 1,
+            std::cmp::Ordering::Equal => return None,
+        };
+        if sudden_death {
+            return Some(leader);
+        }
+        let lead = score[leader] - score[1 - leader];
+        let needed_lead = if self.win_by_two { 2 } else { 1 };
+        (score[leader] >= self.target_score && lead >= needed_lead).then_some(leader)
+    }
+
+    /// Result of a timed game whose clock has just run out.
+    pub fn time_up(&self, score: [u32; 2]) -> TimeUp {
+        match self.game_winner(score, true) {
+            Some(w) => TimeUp::Winner(w),
+            None if self.overtime == Overtime::SuddenDeath => TimeUp::SuddenDeath,
+            None => TimeUp::Draw,
+        }
+    }
+
+    /// Games a player must win to take the series.
+    pub fn games_to_win(&self) -> u32 {
+        self.best_of.max(1) / 2 + 1
+    }
+
+    /// Winner of the series for `games_won`, if it is decided.
+    pub fn series_winner(&self, games_won: [u32; 2]) -> Option<usize> {
+        (0..2).find(|&p| games_won[p] >= self.games_to_win())
+    }
+}
+
+/// The form sent in `CREATE_AUTH` and `CREATE_SOLO`:
+/// `<target score>,<win by two 0|1>,<seconds, 0 if untimed>,<overtime>,<best of>`.
+impl fmt::Display for MatchRules {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{},{},{},{:?},{}",
+            self.target_score,
+            u8::from(self.win_by_two),
+            self.time_limit.unwrap_or(0.0),
+            self.overtime,
+            self.best_of
+        )
+    }
+}
+
+impl FromStr for MatchRules {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad = || format!("bad match rules: {}", s);
+        let [target, by_two, time, overtime, best_of] = s.split(',').collect::<Vec<_>>()[..] else {
+            return Err(bad());
+        };
+        let target_score: u32 = target.parse().map_err(|_| bad())?;
+        let time: f32 = time.parse().map_err(|_| bad())?;
+        let best_of: u32 = best_of.parse().map_err(|_| bad())?;
+        if !(1..=MAX_TARGET_SCORE).contains(&target_score)
+            || !(0.0..=MAX_TIME_LIMIT).contains(&time)
+            || !(1..=MAX_BEST_OF).contains(&best_of)
+        {
+            return Err(bad());
+        }
+        Ok(Self {
+            target_score,
+            win_by_two: match by_two {
+                "0" => false,
+                "1" => true,
+                _ => return Err(bad()),
+            },
+            time_limit: (time > 0.0).then_some(time),
+            overtime: match overtime {
+                "SuddenDeath" => Overtime::SuddenDeath,
+                "Draw" => Overtime::Draw,
+                _ => return Err(bad()),
+            },
+            best_of,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_is_first_to_winning_score() {
+        let rules = MatchRules::default();
+        assert_eq!(rules.game_winner([WINNING_SCORE - 1, 0], false), None);
+        assert_eq!(rules.game_winner([WINNING_SCORE, WINNING_SCORE - 1], false), Some(0));
+        assert_eq!(rules.series_winner([1, 0]), Some(0));
+    }
+
+    #[test]
+    fn test_win_by_two() {
+        let rules = MatchRules { target_score: 3, win_by_two: true, ..Default::default() };
+        assert_eq!(rules.game_winner([3, 2], false), None);
+        assert_eq!(rules.game_winner([5, 3], false), Some(0));
+        assert_eq!(rules.game_winner([4, 6], false), Some(1));
+    }
+
+    #[test]
+    fn test_time_up() {
+        let rules = MatchRules { time_limit: Some(60.0), ..Default::default() };
+        assert_eq!(rules.time_up([2, 1]), TimeUp::Winner(0));
+        assert_eq!(rules.time_up([1, 1]), TimeUp::SuddenDeath);
+        assert_eq!(rules.game_winner([2, 1], true), Some(0));
+
+        let rules = MatchRules { overtime: Overtime::Draw, ..rules };
+        assert_eq!(rules.time_up([1, 1]), TimeUp::Draw);
+    }
+
+    #[test]
+    fn test_rules_round_trip_as_text_within_limits() {
+        let rules = MatchRules { target_score: 3, win_by_two: true, time_limit: Some(180.0), overtime: Overtime::Draw, best_of: 3 };
+        assert_eq!(rules.to_string().parse(), Ok(rules));
+        assert_eq!(MatchRules::default().to_string().parse(), Ok(MatchRules::default()));
+
+        for bad in ["", "6,0,0,SuddenDeath", "0,0,0,SuddenDeath,1", "6,2,0,SuddenDeath,1", "6,0,-5,Draw,1", "6,0,NaN,Draw,1", "6,0,0,Shootout,1", "99,0,0,Draw,1", "6,0,0,Draw,15"] {
+            assert!(bad.parse::<MatchRules>().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_best_of_series() {
+        let rules = MatchRules { best_of: 5, ..Default::default() };
+        assert_eq!(rules.games_to_win(), 3);
+        assert_eq!(rules.series_winner([2, 2]), None);
+        assert_eq!(rules.series_winner([2, 3]), Some(1));
+    }
+}
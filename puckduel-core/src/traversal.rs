@@ -0,0 +1,148 @@
+use std::net::SocketAddr;
+use crate::config::network::{PROBE_ATTEMPTS, PROBE_INITIAL_MS, PROBE_MAX_MS};
+
+/// `Hello` sequence number of a connectivity check.
+pub const PROBE: u32 = 0;
+/// `Hello` sequence number of the answer to one; receiving it proves the path
+/// works both ways.
+pub const PROBE_REPLY: u32 = 1;
+
+/// Candidates as sent in a `CANDIDATES:` command: comma-separated socket
+/// addresses, IPv6 ones in brackets.
+pub fn format_candidates(addrs: &[SocketAddr]) -> String {
+    addrs.iter().map(|a| a.to_string()).collect::<Vec<_>>().join(",")
+}
+
+/// Parses a candidate list, skipping anything malformed. IPv4-mapped IPv6
+/// addresses (as seen by a dual-stack server) become plain IPv4.
+pub fn parse_candidates(list: &str) -> Vec<SocketAddr> {
+    list.split(',')
+        .filter_map(|a| a.trim().parse::<SocketAddr>().ok())
+        .map(|a| SocketAddr::new(a.ip().to_canonical(), a.port()))
+        .filter(|a| a.port() != 0 && !a.ip().is_unspecified())
+        .collect()
+}
+
+/// One address being probed.
+#[derive(Debug)]
+struct Check {
+    addr:     SocketAddr,
+    attempts: u32,
+    /// When the next probe is due, in seconds on the caller's clock.
+    next_at:  f64,
+}
+
+/// ICE-lite connectivity checks: every candidate is probed with `Hello`s,
+/// backing off from `PROBE_INITIAL_MS` to `PROBE_MAX_MS`, until one answers.
+/// The first address to answer becomes the selected P2P path.
+#[derive(Debug, Default)]
+pub struct Prober {
+    checks:   Vec<Check>,
+    selected: Option<SocketAddr>,
+}
+
+impl Prober {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts probing `addr` unless it is already known.
+    pub fn add(&mut self, addr: SocketAddr, now: f64) {
+        let addr = SocketAddr::new(addr.ip().to_canonical(), addr.port());
+        if !self.checks.iter().any(|c| c.addr == addr) {
+            self.checks.push(Check { addr, attempts: 0, next_at: now });
+        }
+    }
+
+    /// Addresses to probe now. Each is rescheduled with a doubled delay, and
+    /// dropped after `PROBE_ATTEMPTS`. Nothing is due once a path is selected.
+    pub fn due(&mut self, now: f64) -> Vec<SocketAddr> {
+        if self.selected.is_some() {
+            return Vec::new();
+        }
+        let mut out = Vec::new();
+        for c in self.checks.iter_mut().filter(|c| c.attempts < PROBE_ATTEMPTS && c.next_at <= now) {
+            let delay = (PROBE_INITIAL_MS << c.attempts.min(16)).min(PROBE_MAX_MS);
+            c.attempts += 1;
+            c.next_at = now + delay as f64 / 1000.0;
+            out.push(c.addr);
+        }
+        out
+    }
+
+    /// A probe reply came back from `addr`.
+    pub fn succeeded(&mut self, addr: SocketAddr) {
+        if self.selected.is_none() {
+            self.selected = Some(SocketAddr::new(addr.ip().to_canonical(), addr.port()));
+        }
+    }
+
+    /// Forgets the selected path (it stopped answering) and probes again.
+    pub fn reset(&mut self, now: f64) {
+        self.selected = None;
+        for c in &mut self.checks {
+            c.attempts = 0;
+            c.next_at = now;
+        }
+    }
+
+    pub fn selected(&self) -> Option<SocketAddr> {
+        self.selected
+    }
+
+    /// There were candidates, and every check has used up its attempts
+    /// without an answer.
+    pub fn exhausted(&self) -> bool {
+        self.selected.is_none() && !self.checks.is_empty() && self.checks.iter().all(|c| c.attempts >= PROBE_ATTEMPTS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_candidates_round_trip() {
+        let addrs: Vec<SocketAddr> = vec!["192.168.1.20:5000".parse().unwrap(), "[2001:db8::1]:6000".parse().unwrap()];
+        assert_eq!(parse_candidates(&format_candidates(&addrs)), addrs);
+        assert_eq!(
+            parse_candidates("[::ffff:203.0.113.7]:4000, junk, 0.0.0.0:1"),
+            vec!["203.0.113.7:4000".parse::<SocketAddr>().unwrap()]
+        );
+    }
+
+    #[test]
+    fn test_probes_back_off_and_stop_once_selected() {
+        let a: SocketAddr = "10.0.0.2:7000".parse().unwrap();
+        let mut p = Prober::new();
+        p.add(a, 0.0);
+        p.add(a, 0.0);
+        assert_eq!(p.due(0.0), vec![a]);
+        assert!(p.due(0.05).is_empty());
+        assert_eq!(p.due(PROBE_INITIAL_MS as f64 / 1000.0), vec![a]);
+        // The second delay is twice the first
+        assert!(p.due(0.25).is_empty());
+        assert_eq!(p.due(0.31), vec![a]);
+
+        p.succeeded(a);
+        assert_eq!(p.selected(), Some(a));
+        assert!(p.due(10.0).is_empty());
+
+        p.reset(10.0);
+        assert_eq!(p.due(10.0), vec![a]);
+    }
+
+    #[test]
+    fn test_gives_up_after_max_attempts() {
+        let mut p = Prober::new();
+        assert!(!p.exhausted());
+        p.add("10.0.0.2:7000".parse().unwrap(), 0.0);
+        let mut t = 0.0;
+        for _ in 0..PROBE_ATTEMPTS {
+            assert_eq!(p.due(t).len(), 1);
+            t += PROBE_MAX_MS as f64 / 1000.0;
+        }
+        assert!(p.due(t + 100.0).is_empty());
+        assert!(p.exhausted());
+    }
+}
@@ -1,12 +1,26 @@
-use std::sync::{Arc, Mutex};
+use std::borrow::Cow;
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::{Duration, Instant};
-use std::net::SocketAddr;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use serde::Serialize;
 use tauri::ipc::Channel;
 use tauri::State;
 use tokio::net::UdpSocket;
 use tokio::sync::Mutex as TokioMutex;
 use puckduel_core::game::{RenderState, GameState};
+use puckduel_core::config::TableGeometry;
+use puckduel_core::rules::MatchRules;
+use puckduel_core::ai::{Difficulty, Personality};
+use puckduel_core::auth::SessionKey;
+use puckduel_core::crypto::{Cipher, Handshake};
+use puckduel_core::protocol::{self, Arrival, MessageType, SequenceTracker};
+use puckduel_core::interpolation::{reconcile_puck, SnapshotBuffer, StateSmoother};
+use puckduel_core::physics::{step_free_puck, Paddle};
+use puckduel_core::rollback::RollbackSession;
+use puckduel_core::config::rollback::MAX_ROLLBACK_FRAMES;
+use puckduel_core::netstats::{Heartbeat, NetPath, NetStats, PingTracker};
+use puckduel_core::traversal::{self, format_candidates, parse_candidates, Prober};
 use puckduel_core::config::*;
 
 /// Tauri-managed state for the game loop (pointer, running flag, etc.)
@@ -21,7 +35,7 @@ impl GameEngine {
         Self {
             running: Arc::new(AtomicBool::new(false)),
             paused:  Arc::new(AtomicBool::new(false)),
-            pointer: Arc::new(Mutex::new([TABLE_WIDTH / 2.0, TABLE_HEIGHT - 120.0])),
+            pointer: Arc::new(Mutex::new(TableGeometry::default().paddle_start(0))),
             task:    Mutex::new(None),
         }
     }
@@ -29,39 +43,257 @@ impl GameEngine {
 
 /// Tauri-managed state for the server UDP connection
 pub struct ServerState {
-    pub socket: Arc<TokioMutex<Option<Arc<UdpSocket>>>>,
+    pub socket: Arc<TokioMutex<Option<Arc<Endpoint>>>>,
     pub room_code: Arc<TokioMutex<Option<String>>>,
-    pub peer_addr: Arc<TokioMutex<Option<String>>>,
+    /// The opponent's P2P candidates, from `PEER` and `CANDIDATES`.
+    pub peer_candidates: Arc<TokioMutex<Vec<SocketAddr>>>,
+    /// The server simulates this match (`START_AUTH`); we only send input.
+    pub authoritative: Arc<TokioMutex<bool>>,
+    /// When START arrived. Both players count rollback frames from it.
+    pub started_at: Arc<TokioMutex<Option<Instant>>>,
+    /// `<room>:<token>` from the server's `TOKEN`, sent back in `REJOIN`.
+    pub reconnect: Arc<TokioMutex<Option<String>>>,
+    /// Set by `rejoin_room`: the match is already under way.
+    pub resume: Arc<TokioMutex<bool>>,
 }
 impl ServerState {
     pub fn new() -> Self {
         Self {
             socket: Arc::new(TokioMutex::new(None)),
             room_code: Arc::new(TokioMutex::new(None)),
-            peer_addr: Arc::new(TokioMutex::new(None)),
+            peer_candidates: Arc::new(TokioMutex::new(Vec::new())),
+            authoritative: Arc::new(TokioMutex::new(false)),
+            started_at: Arc::new(TokioMutex::new(None)),
+            reconnect: Arc::new(TokioMutex::new(None)),
+            resume: Arc::new(TokioMutex::new(false)),
         }
     }
 }
 
+/// Any global unicast IPv6 address; only used to ask the OS for a route.
+const IPV6_ROUTE_TARGET: SocketAddr = SocketAddr::new(IpAddr::V6(Ipv6Addr::new(0x2001, 0x4860, 0x4860, 0, 0, 0, 0, 0x8888)), 53);
+
+/// Our UDP endpoint: the socket registered with the game server plus, when the
+/// server is reached over IPv4, an IPv6 socket for direct IPv6 paths. The server
+/// socket stays unconnected so the peer can reach it through the same NAT
+/// mapping the server observed.
+pub struct Endpoint {
+    sock:        UdpSocket,
+    v6:          Option<UdpSocket>,
+    /// `None` for LAN games, which have no server and so no relay.
+    server:      Option<SocketAddr>,
+    /// The match's session key, from `KEY:` (or the LAN handshake).
+    /// Everything sent once it is known carries its tag.
+    key:         OnceLock<SessionKey>,
+    /// Seal what we send. Sealed packets are opened either way.
+    encrypt:     bool,
+    /// Our key exchange with the server, or with the host on a LAN.
+    handshake:   Handshake,
+    /// Encrypted channel to the server, when `encrypt` is on.
+    server_link: OnceLock<Cipher>,
+    /// Salt for the direct channel's key, from `SALT:` or `REJOINED`; a new
+    /// one arrives whenever either player reconnects.
+    p2p_salt:    Mutex<String>,
+    /// Encrypted channel to the peer, keyed from the session key and salt at
+    /// game start and again on every new salt.
+    p2p_link:    RwLock<Option<Cipher>>,
+}
+
+impl Endpoint {
+    /// With `encrypt`, everything to and from the server goes over a channel
+    /// set up by a handshake before this returns.
+    async fn open(server_addr: &str, encrypt: bool) -> Result<Arc<Self>, String> {
+        let server = tokio::net::lookup_host(server_addr)
+            .await
+            .map_err(|e| format!("resolve: {}", e))?
+            .next()
+            .ok_or("resolve: no address for server")?;
+        let bind = if server.is_ipv6() { "[::]:0" } else { "0.0.0.0:0" };
+        let sock = UdpSocket::bind(bind).await.map_err(|e| format!("bind: {}", e))?;
+        let v6 = if server.is_ipv6() { None } else { UdpSocket::bind("[::]:0").await.ok() };
+        let ep = Self::new(sock, v6, Some(server), encrypt);
+        if encrypt {
+            ep.connect_encrypted().await?;
+        }
+        Ok(Arc::new(ep))
+    }
+
+    /// An IPv4 socket for LAN play, allowed to broadcast announces.
+    async fn lan(encrypt: bool) -> Result<Arc<Self>, String> {
+        let sock = UdpSocket::bind("0.0.0.0:0").await.map_err(|e| format!("bind: {}", e))?;
+        sock.set_broadcast(true).map_err(|e| format!("broadcast: {}", e))?;
+        Ok(Arc::new(Self::new(sock, None, None, encrypt)))
+    }
+
+    fn new(sock: UdpSocket, v6: Option<UdpSocket>, server: Option<SocketAddr>, encrypt: bool) -> Self {
+        Self {
+            sock,
+            v6,
+            server,
+            key: OnceLock::new(),
+            encrypt,
+            handshake: Handshake::new(rand::random()),
+            server_link: OnceLock::new(),
+            p2p_salt: Mutex::new(String::new()),
+            p2p_link: RwLock::new(None),
+        }
+    }
+
+    /// Sends our handshake until the server answers with its own, once a second
+    /// for up to three tries.
+    async fn connect_encrypted(&self) -> Result<(), String> {
+        let server = self.server.ok_or("no server to encrypt to")?;
+        let mut buf = [0u8; 256];
+        for _ in 0..3 {
+            self.sock.send_to(&self.handshake.packet(), server).await.map_err(|e| format!("send: {}", e))?;
+            let deadline = Instant::now() + Duration::from_secs(1);
+            while let Ok(Ok((n, src))) = tokio::time::timeout(deadline.saturating_duration_since(Instant::now()), self.sock.recv_from(&mut buf)).await {
+                if src != server {
+                    continue;
+                }
+                let pkt = protocol::decode(&buf[..n]).map_err(|e| e.to_string())?;
+                if pkt.header.kind == MessageType::Handshake {
+                    let cipher = self.handshake.finish(pkt.payload, true).ok_or("encryption handshake failed")?;
+                    let _ = self.server_link.set(cipher);
+                    return Ok(());
+                }
+            }
+        }
+        Err("timeout waiting for server".into())
+    }
+
+    /// Repeats our handshake in case the server now sees us on a new address
+    /// (e.g. after switching networks); the next packet we seal moves our
+    /// channel there. The answer is the same key as before, so it needs no
+    /// handling.
+    async fn refresh_channel(&self) {
+        if let (Some(server), Some(_)) = (self.server, self.server_link.get()) {
+            let _ = self.sock.send_to(&self.handshake.packet(), server).await;
+        }
+    }
+
+    /// Starts sealing direct traffic, which needs the session key and our role,
+    /// on a channel keyed with the current salt.
+    fn start_p2p(&self, is_host: bool) {
+        if let Some(key) = self.key.get() {
+            let salt = self.p2p_salt.lock().unwrap();
+            *self.p2p_link.write().unwrap() = Some(key.cipher(is_host, salt.as_bytes()));
+        }
+    }
+
+    fn set_salt(&self, salt: &str) {
+        *self.p2p_salt.lock().unwrap() = salt.trim().to_string();
+    }
+
+    fn set_key(&self, hex: &str) -> Result<(), String> {
+        let key = SessionKey::from_hex(hex).ok_or("malformed session key")?;
+        let _ = self.key.set(key);
+        Ok(())
+    }
+
+    /// `data` as it goes out to the server or to the peer: tagged with the
+    /// session key once there is one, then sealed if we encrypt that link.
+    fn outgoing(&self, data: &[u8], to_server: bool) -> Vec<u8> {
+        let tagged = match self.key.get() {
+            Some(key) => key.sign(data),
+            None => data.to_vec(),
+        };
+        let sealed = if to_server {
+            self.server_link.get().map(|cipher| cipher.seal(&tagged))
+        } else if self.encrypt {
+            self.p2p_link.read().unwrap().as_ref().map(|cipher| cipher.seal(&tagged))
+        } else {
+            None
+        };
+        sealed.unwrap_or(tagged)
+    }
+
+    /// The packet in `data` as received from the server or the peer: opened if
+    /// it came sealed, or `None` if it cannot be.
+    fn incoming<'a>(&self, data: &'a [u8], from_server: bool) -> Option<Cow<'a, [u8]>> {
+        match protocol::decode(data) {
+            Ok(pkt) if pkt.header.kind == MessageType::Sealed => {
+                let opened = if from_server {
+                    self.server_link.get()?.open(pkt.payload)
+                } else {
+                    self.p2p_link.read().unwrap().as_ref()?.open(pkt.payload)
+                };
+                opened.map(Cow::Owned)
+            }
+            _ => Some(Cow::Borrowed(data)),
+        }
+    }
+
+    /// Send to the game server (which relays game traffic to the opponent).
+    async fn send(&self, data: &[u8]) -> std::io::Result<usize> {
+        match self.server {
+            Some(server) => self.sock.send_to(&self.outgoing(data, true), server).await,
+            None => Err(std::io::ErrorKind::NotConnected.into()),
+        }
+    }
+
+    /// Send straight to the peer at `addr` from the socket of its address family.
+    async fn send_to(&self, data: &[u8], addr: SocketAddr) {
+        let main_v6 = self.server.is_some_and(|s| s.is_ipv6());
+        let sock = if addr.is_ipv6() == main_v6 { Some(&self.sock) } else if addr.is_ipv6() { self.v6.as_ref() } else { None };
+        if let Some(sock) = sock {
+            let _ = sock.send_to(&self.outgoing(data, false), addr).await;
+        }
+    }
+
+    /// Addresses the peer may reach us on besides the public one the server
+    /// sees: the LAN address of the server socket and our global IPv6 address.
+    fn candidates(&self) -> Vec<SocketAddr> {
+        let mut out = Vec::new();
+        if let (Some(ip), Ok(local)) = (self.server.and_then(route_ip), self.sock.local_addr()) {
+            out.push(SocketAddr::new(ip, local.port()));
+        }
+        if let Some(v6) = &self.v6 {
+            let global = route_ip(IPV6_ROUTE_TARGET).filter(|ip| matches!(ip, IpAddr::V6(v6) if !v6.is_unicast_link_local()));
+            if let (Some(ip), Ok(local)) = (global, v6.local_addr()) {
+                out.push(SocketAddr::new(ip, local.port()));
+            }
+        }
+        out
+    }
+}
+
+/// The local address the OS would send to `target` from. Connecting a UDP
+/// socket sends nothing, so this costs no traffic.
+fn route_ip(target: SocketAddr) -> Option<IpAddr> {
+    let any: IpAddr = if target.is_ipv6() { Ipv6Addr::UNSPECIFIED.into() } else { Ipv4Addr::UNSPECIFIED.into() };
+    let sock = std::net::UdpSocket::bind((any, 0)).ok()?;
+    sock.connect(target).ok()?;
+    let ip = sock.local_addr().ok()?.ip();
+    (!ip.is_unspecified() && !ip.is_loopback()).then_some(ip)
+}
+
 // ─── Commands ────────────────────────────────────────────────────────────────
 
 /// Create a room on the game server. Returns the 4-digit room code.
+/// `authoritative` asks the server to simulate the match (ranked play) under
+/// `rules`; otherwise the host passes its rules to `start_game`.
+/// `encrypted` (on unless `false`) encrypts our traffic, here and in every
+/// command that connects.
 #[tauri::command]
-pub async fn create_room(server: State<'_, ServerState>, server_addr: String) -> Result<String, String> {
-    let sock = Arc::new(UdpSocket::bind("0.0.0.0:0").await.map_err(|e| format!("bind: {}", e))?);
-    sock.connect(&server_addr).await.map_err(|e| format!("connect: {}", e))?;
+pub async fn create_room(
+    server: State<'_, ServerState>,
+    server_addr: String,
+    authoritative: Option<bool>,
+    rules: Option<MatchRules>,
+    encrypted: Option<bool>,
+) -> Result<String, String> {
+    let sock = Endpoint::open(&server_addr, encrypted.unwrap_or(true)).await?;
 
-    sock.send(b"CREATE").await.map_err(|e| format!("send: {}", e))?;
+    let cmd = if authoritative.unwrap_or(false) { format!("CREATE_AUTH:{}", rules.unwrap_or_default()) } else { "CREATE".to_string() };
+    send_command(&sock, &cmd).await?;
 
-    let mut buf = [0u8; 64];
-    let n = tokio::time::timeout(Duration::from_secs(5), sock.recv(&mut buf))
-        .await
-        .map_err(|_| "timeout waiting for server")?
-        .map_err(|e| format!("recv: {}", e))?;
-    let resp = String::from_utf8(buf[..n].to_vec()).map_err(|_| "invalid response")?;
+    let resp = recv_command(&sock, 5).await?;
 
     if let Some(code) = resp.strip_prefix("CREATED:") {
         let code = code.trim().to_string();
+        // Handed to the joiner along with our public address once they arrive
+        send_command(&sock, &format!("CANDIDATES:{}", format_candidates(&sock.candidates()))).await?;
         *server.socket.lock().await = Some(sock);
         *server.room_code.lock().await = Some(code.clone());
         Ok(code)
@@ -70,59 +302,117 @@ pub async fn create_room(server: State<'_, ServerState>, server_addr: String) ->
     }
 }
 
-/// Wait for a specific text response, skipping PEER messages. Stores peer addr.
-async fn recv_until(sock: &UdpSocket, peer_addr: &TokioMutex<Option<String>>, target: &str, timeout_secs: u64) -> Result<(), String> {
-    let mut buf = [0u8; 256];
+async fn send_command(sock: &Endpoint, text: &str) -> Result<(), String> {
+    sock.send(&protocol::encode_command(text)).await.map_err(|e| format!("send: {}", e))?;
+    Ok(())
+}
+
+/// Wait for the next command from the server. A server on another protocol
+/// version fails here with a clear "please update" error. Once we hold the
+/// session key, commands without its tag are skipped.
+async fn recv_command(sock: &Endpoint, timeout_secs: u64) -> Result<String, String> {
+    let mut buf = [0u8; 512];
     loop {
-        let n = tokio::time::timeout(Duration::from_secs(timeout_secs), sock.recv(&mut buf))
+        let (n, src) = tokio::time::timeout(Duration::from_secs(timeout_secs), sock.sock.recv_from(&mut buf))
             .await
-            .map_err(|_| format!("timeout waiting for {}", target))?
+            .map_err(|_| "timeout waiting for server")?
             .map_err(|e| format!("recv: {}", e))?;
-        let resp = String::from_utf8(buf[..n].to_vec()).map_err(|_| "invalid response")?;
-        let trimmed = resp.trim();
-        if trimmed == target {
-            return Ok(());
+        // Early P2P probes from the peer are answered once the game starts
+        if Some(src) != sock.server {
+            continue;
+        }
+        let Some(data) = sock.incoming(&buf[..n], true) else { continue };
+        let pkt = protocol::decode(&data).map_err(|e| e.to_string())?;
+        if sock.key.get().is_some_and(|k| k.verify(&data).is_none()) {
+            continue;
+        }
+        if let Some(cmd) = pkt.command() {
+            return Ok(cmd.to_string());
+        }
+    }
+}
+
+/// Wait for one of `targets`, skipping PEER, CANDIDATES, TOKEN, KEY and SALT
+/// messages. Stores the peer's candidates, the reconnect token, the session key
+/// and the direct channel's salt and returns the message that matched.
+async fn recv_until(sock: &Endpoint, server: &ServerState, targets: &[&str], timeout_secs: u64) -> Result<String, String> {
+    loop {
+        let resp = recv_command(sock, timeout_secs)
+            .await
+            .map_err(|e| if e.starts_with("timeout") { format!("timeout waiting for {}", targets.join("/")) } else { e })?;
+        if targets.contains(&resp.as_str()) {
+            return Ok(resp);
         }
-        if let Some(peer) = trimmed.strip_prefix("PEER:") {
-            *peer_addr.lock().await = Some(peer.to_string());
+        if let Some(list) = resp.strip_prefix("PEER:").or_else(|| resp.strip_prefix("CANDIDATES:")) {
+            server.peer_candidates.lock().await.extend(parse_candidates(list));
+        }
+        if let Some(token) = resp.strip_prefix("TOKEN:") {
+            *server.reconnect.lock().await = Some(token.to_string());
+        }
+        if let Some(key) = resp.strip_prefix("KEY:") {
+            sock.set_key(key)?;
+        }
+        if let Some(salt) = resp.strip_prefix("SALT:") {
+            sock.set_salt(salt);
         }
     }
 }
 
-/// Join an existing room on the game server. Returns when the game starts.
+/// Join an existing room on the game server. Returns when the game starts,
+/// with the token `rejoin_room` needs should this app drop out mid-match.
 #[tauri::command]
-pub async fn join_room(server: State<'_, ServerState>, server_addr: String, room_code: String) -> Result<(), String> {
-    let sock = Arc::new(UdpSocket::bind("0.0.0.0:0").await.map_err(|e| format!("bind: {}", e))?);
-    sock.connect(&server_addr).await.map_err(|e| format!("connect: {}", e))?;
+pub async fn join_room(
+    server: State<'_, ServerState>,
+    server_addr: String,
+    room_code: String,
+    encrypted: Option<bool>,
+) -> Result<Option<String>, String> {
+    let sock = Endpoint::open(&server_addr, encrypted.unwrap_or(true)).await?;
 
-    let join_cmd = format!("JOIN:{}", room_code.trim());
-    sock.send(join_cmd.as_bytes()).await.map_err(|e| format!("send: {}", e))?;
+    send_command(&sock, &format!("JOIN:{}", room_code.trim())).await?;
 
-    recv_until(&sock, &server.peer_addr, "JOINED", 5).await?;
-    recv_until(&sock, &server.peer_addr, "START", 10).await?;
+    recv_until(&sock, &server, &["JOINED"], 5).await?;
+    send_command(&sock, &format!("CANDIDATES:{}", format_candidates(&sock.candidates()))).await?;
+    let start = recv_until(&sock, &server, &["START", "START_AUTH"], 10).await?;
+
+    *server.authoritative.lock().await = start == "START_AUTH";
+    *server.started_at.lock().await = Some(Instant::now());
 
     *server.socket.lock().await = Some(sock);
     *server.room_code.lock().await = Some(room_code);
-    Ok(())
+    Ok(server.reconnect.lock().await.clone())
 }
 
-/// Create a solo game (vs AI) on the game server. Returns when game starts.
+/// Create a solo game on the game server, which runs the AI opponent and the
+/// physics under `rules`. Returns when game starts.
 #[tauri::command]
-pub async fn create_solo(server: State<'_, ServerState>, server_addr: String) -> Result<(), String> {
-    let sock = Arc::new(UdpSocket::bind("0.0.0.0:0").await.map_err(|e| format!("bind: {}", e))?);
-    sock.connect(&server_addr).await.map_err(|e| format!("connect: {}", e))?;
+pub async fn create_solo(
+    server: State<'_, ServerState>,
+    server_addr: String,
+    difficulty: Option<Difficulty>,
+    personality: Option<Personality>,
+    rules: Option<MatchRules>,
+    encrypted: Option<bool>,
+) -> Result<(), String> {
+    let sock = Endpoint::open(&server_addr, encrypted.unwrap_or(true)).await?;
 
-    sock.send(b"CREATE_SOLO").await.map_err(|e| format!("send: {}", e))?;
+    let cmd = format!(
+        "CREATE_SOLO:{:?}:{:?}:{}",
+        difficulty.unwrap_or_default(),
+        personality.unwrap_or_default(),
+        rules.unwrap_or_default()
+    );
+    send_command(&sock, &cmd).await?;
 
-    let mut buf = [0u8; 64];
-    let n = tokio::time::timeout(Duration::from_secs(5), sock.recv(&mut buf))
-        .await
-        .map_err(|_| "timeout waiting for server")?
-        .map_err(|e| format!("recv: {}", e))?;
-    let resp = String::from_utf8(buf[..n].to_vec()).map_err(|_| "invalid response")?;
+    let mut resp = recv_command(&sock, 5).await?;
+    if let Some(key) = resp.strip_prefix("KEY:") {
+        sock.set_key(key)?;
+        resp = recv_command(&sock, 5).await?;
+    }
 
-    if resp.trim() == "START" {
+    if resp == "START_AUTH" {
         *server.socket.lock().await = Some(sock);
+        *server.authoritative.lock().await = true;
         Ok(())
     } else {
         Err(format!("unexpected response: {}", resp))
@@ -130,125 +420,613 @@ pub async fn create_solo(server: State<'_, ServerState>, server_addr: String) ->
 }
 
 /// Wait for opponent to join. Blocks until START received from server (skips PEER).
+/// Returns the reconnect token, like `join_room`.
 #[tauri::command]
-pub async fn wait_for_opponent(server: State<'_, ServerState>) -> Result<(), String> {
+pub async fn wait_for_opponent(server: State<'_, ServerState>) -> Result<Option<String>, String> {
     let sock = server.socket.lock().await.take().ok_or("not connected")?;
-    recv_until(&sock, &server.peer_addr, "START", 120).await?;
+    let start = recv_until(&sock, &server, &["START", "START_AUTH"], 120).await?;
+    *server.authoritative.lock().await = start == "START_AUTH";
+    *server.started_at.lock().await = Some(Instant::now());
+    *server.socket.lock().await = Some(sock);
+    Ok(server.reconnect.lock().await.clone())
+}
+
+/// Rejoin a match this app dropped out of, using the token from `join_room` or
+/// `wait_for_opponent`. Returns whether we are the host; `start_game` then
+/// resumes from the state the match is in.
+#[tauri::command]
+pub async fn rejoin_room(
+    server: State<'_, ServerState>,
+    server_addr: String,
+    token: String,
+    encrypted: Option<bool>,
+) -> Result<bool, String> {
+    let sock = Endpoint::open(&server_addr, encrypted.unwrap_or(true)).await?;
+
+    send_command(&sock, &format!("REJOIN:{}", token)).await?;
+
+    // REJOINED:<slot>:<ms since START>:<START|START_AUTH>:<session key>:<salt>
+    let resp = recv_command(&sock, 5).await?;
+    let parts: Vec<&str> = resp.strip_prefix("REJOINED:").ok_or(format!("rejoin refused: {}", resp))?.split(':').collect();
+    let [slot, elapsed_ms, mode, key, salt] = parts[..] else {
+        return Err(format!("unexpected response: {}", resp));
+    };
+    let elapsed = Duration::from_millis(elapsed_ms.parse().map_err(|_| format!("unexpected response: {}", resp))?);
+    sock.set_key(key)?;
+    sock.set_salt(salt);
+
+    if mode == "START" {
+        send_command(&sock, &format!("CANDIDATES:{}", format_candidates(&sock.candidates()))).await?;
+    }
+    *server.authoritative.lock().await = mode == "START_AUTH";
+    *server.started_at.lock().await = Some(Instant::now().checked_sub(elapsed).unwrap_or_else(Instant::now));
+    *server.reconnect.lock().await = Some(token);
+    *server.resume.lock().await = true;
     *server.socket.lock().await = Some(sock);
+    Ok(slot == "0")
+}
+
+// ─── LAN ─────────────────────────────────────────────────────────────────────
+
+/// A game found by `discover_lan`.
+#[derive(Clone, Serialize)]
+pub struct LanGame {
+    pub name: String,
+    /// The host's address, to pass to `join_lan`.
+    pub addr: String,
+}
+
+/// Records a LAN opponent the way a server START would, so `start_game` runs
+/// the split-authority loop straight to them.
+async fn start_lan_match(server: &ServerState, ep: Arc<Endpoint>, peer: SocketAddr) {
+    *server.peer_candidates.lock().await = vec![peer];
+    *server.authoritative.lock().await = false;
+    *server.started_at.lock().await = Some(Instant::now());
+    *server.socket.lock().await = Some(ep);
+}
+
+/// The `Announce` a LAN host broadcasts, and the name it carries: trimmed,
+/// capped at `LAN_NAME_MAX_CHARS` and never empty.
+fn lan_announce(name: &str) -> (String, Vec<u8>) {
+    let name: String = name.trim().chars().take(network::LAN_NAME_MAX_CHARS).collect();
+    let name = if name.is_empty() { "Puck Duel".to_string() } else { name };
+    let packet = protocol::encode(MessageType::Announce, 0, name.as_bytes());
+    (name, packet)
+}
+
+/// The game a datagram from `src` announces, if it is an `Announce` on our
+/// protocol version.
+fn parse_lan_announce(data: &[u8], src: SocketAddr) -> Option<LanGame> {
+    match protocol::decode(data) {
+        Ok(pkt) => pkt.announce().map(|name| LanGame { name: name.to_string(), addr: src.to_string() }),
+        Err(e) => {
+            log::info!("ignoring LAN game at {}: {}", src, e);
+            None
+        }
+    }
+}
+
+/// Host a game on the local network without any server: broadcasts `name`
+/// every `LAN_ANNOUNCE_INTERVAL_MS` until a player sends its `Handshake`, then
+/// answers with ours and returns as the game starts. The key exchange gives
+/// both players the session key, so there is no server needed to issue one.
+#[tauri::command]
+pub async fn host_lan(server: State<'_, ServerState>, name: String, encrypted: Option<bool>) -> Result<(), String> {
+    let ep = Endpoint::lan(encrypted.unwrap_or(true)).await?;
+    let (name, announce) = lan_announce(&name);
+    let broadcast = SocketAddr::from((Ipv4Addr::BROADCAST, network::LAN_DISCOVERY_PORT));
+    let interval = Duration::from_millis(network::LAN_ANNOUNCE_INTERVAL_MS);
+    let deadline = Instant::now() + Duration::from_secs(120);
+    let mut buf = [0u8; 256];
+
+    let (peer, key) = 'announce: loop {
+        if Instant::now() > deadline {
+            return Err("timeout waiting for a player to join".into());
+        }
+        ep.sock.send_to(&announce, broadcast).await.map_err(|e| format!("broadcast: {}", e))?;
+        let next = Instant::now() + interval;
+        while let Ok(Ok((n, src))) = tokio::time::timeout(next.saturating_duration_since(Instant::now()), ep.sock.recv_from(&mut buf)).await {
+            let Ok(pkt) = protocol::decode(&buf[..n]) else { continue };
+            if pkt.header.kind != MessageType::Handshake {
+                continue;
+            }
+            if let Some(secret) = ep.handshake.agree(pkt.payload) {
+                break 'announce (src, SessionKey::from_bytes(secret));
+            }
+        }
+    };
+
+    let reply = ep.handshake.packet();
+    let _ = ep.sock.send_to(&reply, peer).await;
+    let _ = ep.key.set(key);
+    log::info!("LAN game \"{}\" joined by {}", name, peer);
+    start_lan_match(&server, ep.clone(), peer).await;
+
+    // The joiner repeats its handshake every 500ms until ours gets through
+    let linger = Instant::now() + Duration::from_millis(600);
+    while let Ok(Ok((n, src))) = tokio::time::timeout(linger.saturating_duration_since(Instant::now()), ep.sock.recv_from(&mut buf)).await {
+        if src == peer && protocol::decode(&buf[..n]).is_ok_and(|p| p.header.kind == MessageType::Handshake) {
+            let _ = ep.sock.send_to(&reply, peer).await;
+        }
+    }
+    Ok(())
+}
+
+/// Listen for `LAN_DISCOVERY_WINDOW_MS` and list the games announced on the
+/// local network. Hosts on another protocol version are left out.
+#[tauri::command]
+pub async fn discover_lan() -> Result<Vec<LanGame>, String> {
+    let sock = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, network::LAN_DISCOVERY_PORT))
+        .await
+        .map_err(|e| format!("bind: {}", e))?;
+    let deadline = Instant::now() + Duration::from_millis(network::LAN_DISCOVERY_WINDOW_MS);
+    let mut games: Vec<LanGame> = Vec::new();
+    let mut buf = [0u8; 256];
+
+    while let Ok(Ok((n, src))) = tokio::time::timeout(deadline.saturating_duration_since(Instant::now()), sock.recv_from(&mut buf)).await {
+        if let Some(game) = parse_lan_announce(&buf[..n], src).filter(|g| !games.iter().any(|h| h.addr == g.addr)) {
+            games.push(game);
+        }
+    }
+    Ok(games)
+}
+
+/// Join a game found by `discover_lan`: sends our `Handshake` until the host
+/// answers with its own, and returns with the session key agreed.
+#[tauri::command]
+pub async fn join_lan(server: State<'_, ServerState>, addr: String, encrypted: Option<bool>) -> Result<(), String> {
+    let peer: SocketAddr = addr.parse().map_err(|_| format!("bad address: {}", addr))?;
+    let ep = Endpoint::lan(encrypted.unwrap_or(true)).await?;
+    let join = ep.handshake.packet();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let mut buf = [0u8; 256];
+
+    'join: loop {
+        if Instant::now() > deadline {
+            return Err("timeout waiting for the host".into());
+        }
+        ep.sock.send_to(&join, peer).await.map_err(|e| format!("send: {}", e))?;
+        let next = Instant::now() + Duration::from_millis(500);
+        while let Ok(Ok((n, src))) = tokio::time::timeout(next.saturating_duration_since(Instant::now()), ep.sock.recv_from(&mut buf)).await {
+            let Ok(pkt) = protocol::decode(&buf[..n]) else { continue };
+            if src != peer || pkt.header.kind != MessageType::Handshake {
+                continue;
+            }
+            let secret = ep.handshake.agree(pkt.payload).ok_or("the host sent a bad handshake")?;
+            let _ = ep.key.set(SessionKey::from_bytes(secret));
+            break 'join;
+        }
+    }
+
+    start_lan_match(&server, ep, peer).await;
     Ok(())
 }
 
-/// Try to establish a P2P connection to the peer.
-/// Returns (p2p_socket, peer_actual_addr) on success.
-async fn try_p2p(peer_addr_str: &str, timeout_secs: u64) -> Option<(Arc<UdpSocket>, SocketAddr)> {
-    let parts: Vec<&str> = peer_addr_str.split(':').collect();
-    if parts.len() != 2 { return None; }
-    let peer_ip: std::net::IpAddr = parts[0].parse().ok()?;
-    let peer_port: u16 = parts[1].parse().ok()?;
-    let relay_addr: SocketAddr = (peer_ip, peer_port).into();
+/// The direct path to the opponent, found and kept up with ICE-lite checks.
+/// Game traffic moves onto it once it answers and falls back to the relay if
+/// it stops, so a match can migrate from relay to P2P at any point.
+struct PeerPath {
+    clock:   Instant,
+    prober:  Prober,
+    /// When the selected path last proved itself (a probe reply or a pong).
+    last_ok: Option<Instant>,
+    /// Every candidate went unanswered; play stays on the relay.
+    gave_up: bool,
+}
+
+impl PeerPath {
+    fn new(candidates: &[SocketAddr]) -> Self {
+        let mut prober = Prober::new();
+        for &addr in candidates {
+            prober.add(addr, 0.0);
+        }
+        Self { clock: Instant::now(), prober, last_ok: None, gave_up: false }
+    }
+
+    fn now(&self) -> f64 {
+        self.clock.elapsed().as_secs_f64()
+    }
+
+    fn addr(&self) -> Option<SocketAddr> {
+        self.prober.selected()
+    }
+
+    /// The selected path answered within `PING_TIMEOUT_MS`.
+    fn is_healthy(&self) -> bool {
+        self.last_ok.is_some_and(|t| t.elapsed() < Duration::from_millis(network::PING_TIMEOUT_MS))
+    }
+
+    /// Takes in what a receive poll learned: new candidates, probes from the
+    /// peer (whose source address is a candidate too) and answers to ours.
+    fn update(&mut self, received: &Received) {
+        let now = self.now();
+        for &addr in received.candidates.iter().chain(&received.probes) {
+            self.prober.add(addr, now);
+        }
+        for &addr in &received.probe_replies {
+            if self.prober.selected().is_none() {
+                log::info!("P2P path to {} is up", addr);
+            }
+            self.prober.succeeded(addr);
+            if self.prober.selected() == Some(addr) {
+                self.last_ok = Some(Instant::now());
+            }
+        }
+        if received.pongs.iter().any(|&(path, _)| path == NetPath::P2p) {
+            self.last_ok = Some(Instant::now());
+        }
+    }
+
+    /// Sends the probes that are due. A selected path that has been silent for
+    /// `HEARTBEAT_TIMEOUT_MS` is dropped and every candidate is probed again.
+    /// Once every candidate has gone unanswered the match stays on the relay,
+    /// which `send_to_opponent` already uses while there is no healthy path;
+    /// a new candidate from the peer starts probing again.
+    async fn probe(&mut self, ep: &Endpoint) {
+        let now = self.now();
+        let stale = self.last_ok.is_none_or(|t| t.elapsed() > Duration::from_millis(network::HEARTBEAT_TIMEOUT_MS));
+        if self.prober.selected().is_some() && stale {
+            log::info!("P2P path lost, probing again");
+            self.prober.reset(now);
+            self.last_ok = None;
+        }
+        let probe = protocol::encode(MessageType::Hello, traversal::PROBE, &[]);
+        for addr in self.prober.due(now) {
+            ep.send_to(&probe, addr).await;
+        }
+        let exhausted = self.prober.exhausted();
+        if exhausted && !self.gave_up {
+            log::info!("no P2P path to the peer after {} probes per candidate: staying on the relay", network::PROBE_ATTEMPTS);
+        }
+        self.gave_up = exhausted;
+    }
+}
+
+/// Send to the opponent over the direct path when there is one, and through
+/// the relay as well until that path has proved itself.
+async fn send_to_opponent(data: &[u8], ep: &Endpoint, path: &PeerPath) {
+    if let Some(addr) = path.addr() {
+        ep.send_to(data, addr).await;
+    }
+    if !path.is_healthy() {
+        let _ = ep.send(data).await;
+    }
+}
 
-    let p2p = Arc::new(UdpSocket::bind("0.0.0.0:0").await.ok()?);
-    // Send hole-punch packet to peer (address from relay server)
-    let _ = p2p.send_to(b"P2P_HELLO", relay_addr).await.ok()?;
+/// Sequence tracking for the input and state streams we receive. Every packet
+/// arrives twice (P2P and relay), so only the newest of each is applied.
+#[derive(Default)]
+struct PeerStreams {
+    input:    SequenceTracker,
+    state:    SequenceTracker,
+    /// Packets dropped for a missing or wrong session-key tag, or sealing.
+    rejected: u64,
+}
+
+impl PeerStreams {
+    fn log_loss(&self) {
+        log::info!(
+            "packet loss: input {:.1}% of {}, state {:.1}% of {} ({} late, {} duplicate, {} rejected)",
+            self.input.loss_ratio() * 100.0,
+            self.input.expected(),
+            self.state.loss_ratio() * 100.0,
+            self.state.expected(),
+            self.input.late + self.state.late,
+            self.input.duplicates + self.state.duplicates,
+            self.rejected,
+        );
+    }
 
-    // Wait for response — the peer's actual address (post-NAT) comes from recv_from
-    let mut buf = [0u8; 32];
-    match tokio::time::timeout(Duration::from_secs(timeout_secs), p2p.recv_from(&mut buf)).await {
-        Ok(Ok((n, peer_actual))) if n >= 9 && &buf[..9] == b"P2P_HELLO" => {
-            // Send a confirmation so peer knows we're alive
-            let _ = p2p.send_to(b"P2P_HELLO", peer_actual).await;
-            Some((p2p, peer_actual))
+    /// Counts a packet dropped for failing authentication, logging the first.
+    fn reject(&mut self, kind: MessageType, src: SocketAddr) {
+        self.rejected += 1;
+        if self.rejected == 1 {
+            log::info!("rejecting unauthenticated {:?} from {}", kind, src);
         }
-        _ => None,
     }
 }
 
-/// Send data to opponent: via P2P send_to and relay send.
-async fn send_to_opponent(data: &[u8], p2p: &Option<(Arc<UdpSocket>, SocketAddr)>, relay: &UdpSocket) {
-    if let Some((ref p2p_sock, peer_addr)) = *p2p {
-        let _ = p2p_sock.send_to(data, peer_addr).await;
+/// Pings the opponent over each transport and reports on the one in use.
+/// Also watches for the other end going silent.
+struct LinkMonitor {
+    clock:       Instant,
+    last_ping:   Option<Instant>,
+    p2p:         PingTracker,
+    relay:       PingTracker,
+    heartbeat:   Heartbeat,
+    last_rejoin: Option<Instant>,
+}
+
+impl LinkMonitor {
+    fn new() -> Self {
+        Self {
+            clock:       Instant::now(),
+            last_ping:   None,
+            p2p:         PingTracker::new(),
+            relay:       PingTracker::new(),
+            heartbeat:   Heartbeat::new(0.0),
+            last_rejoin: None,
+        }
+    }
+
+    fn now(&self) -> f64 {
+        self.clock.elapsed().as_secs_f64()
+    }
+
+    /// Sends a ping on every transport once per `PING_INTERVAL_MS`. Returns
+    /// true when it did, which is also when fresh stats are worth reporting.
+    async fn ping(&mut self, ep: &Endpoint, p2p: Option<SocketAddr>) -> bool {
+        if self.last_ping.is_some_and(|t| t.elapsed() < Duration::from_millis(network::PING_INTERVAL_MS)) {
+            return false;
+        }
+        self.last_ping = Some(Instant::now());
+        let now = self.now();
+        if let Some(addr) = p2p {
+            let ping = protocol::encode(MessageType::Ping, self.p2p.send(now), &[]);
+            ep.send_to(&ping, addr).await;
+        }
+        // Relay pings also keep our room alive on the server while on P2P
+        let ping = protocol::encode(MessageType::Ping, self.relay.send(now), &[]);
+        let _ = ep.send(&ping).await;
+        true
     }
-    let _ = relay.send(data).await;
+
+    fn heard(&mut self) {
+        let now = self.now();
+        self.heartbeat.heard(now);
+    }
+
+    fn is_away(&self) -> bool {
+        self.heartbeat.is_away(self.now())
+    }
+
+    /// The other end has been gone for the whole reconnect grace period.
+    fn expired(&self) -> bool {
+        self.heartbeat.expired(self.now())
+    }
+
+    /// Re-registers with the server once per `REJOIN_INTERVAL_MS`, in case it
+    /// is our address that changed (e.g. after switching networks).
+    async fn rejoin(&mut self, ep: &Endpoint, token: &str) {
+        if self.last_rejoin.is_some_and(|t| t.elapsed() < Duration::from_millis(network::REJOIN_INTERVAL_MS)) {
+            return;
+        }
+        self.last_rejoin = Some(Instant::now());
+        ep.refresh_channel().await;
+        let _ = ep.send(&protocol::encode_command(&format!("REJOIN:{}", token))).await;
+    }
+
+    fn pong(&mut self, path: NetPath, id: u32) {
+        let now = self.now();
+        match path {
+            NetPath::P2p => self.p2p.receive(id, now),
+            _ => self.relay.receive(id, now),
+        }
+    }
+
+    /// Figures for the direct path while game traffic uses it, otherwise for
+    /// the relay (or the game server when it runs the match).
+    fn stats(&self, server_run: bool, direct: bool, streams: &PeerStreams) -> NetStats {
+        let mut stats = if server_run {
+            self.relay.stats(NetPath::Server)
+        } else if direct {
+            self.p2p.stats(NetPath::P2p)
+        } else {
+            self.relay.stats(NetPath::Relay)
+        };
+        stats.state_loss = streams.state.loss_ratio();
+        stats.forfeit_in = self.heartbeat.forfeit_in(self.now());
+        stats
+    }
+}
+
+/// What one poll of the sockets brought in.
+#[derive(Default)]
+struct Received {
+    /// Newest authoritative state, if any.
+    state:         Option<RenderState>,
+    /// Every first-seen opponent input as (frame, pointer), for rollback.
+    inputs:        Vec<(u32, [f32; 2])>,
+    /// Answers to our pings as (transport, ping id).
+    pongs:         Vec<(NetPath, u32)>,
+    game_over:     bool,
+    /// The server says the opponent quit with `LEAVE`.
+    opponent_left: bool,
+    /// Game traffic came in; the server's own replies to commands don't count.
+    heard:         bool,
+    /// Peer addresses from `PEER`/`CANDIDATES` forwarded by the server.
+    candidates:    Vec<SocketAddr>,
+    /// A new salt for the direct channel: the opponent reconnected.
+    salt:          Option<String>,
+    /// Sources of the peer's P2P probes, and of answers to ours.
+    probes:        Vec<SocketAddr>,
+    probe_replies: Vec<SocketAddr>,
 }
 
-/// Receive from both P2P (first) and relay. Returns authoritative state if any.
+/// Receive everything waiting on our sockets. Packets from the game server are
+/// relay traffic; anything else is the peer directly, accepted only from the
+/// selected `p2p` path apart from probes and pings, which are answered on the
+/// socket and address they came from. Sealed packets are opened first. Then
+/// everything must carry the session key's tag, the server's commands included,
+/// except the server's handshake answer; the rest is counted in
+/// `streams.rejected` and dropped.
 async fn recv_from_both(
-    p2p: &Option<(Arc<UdpSocket>, SocketAddr)>,
-    relay: &UdpSocket,
+    ep: &Endpoint,
+    p2p: Option<SocketAddr>,
     opp_ptr: &mut [f32; 2],
-) -> (Option<RenderState>, bool) {
-    let mut received_state: Option<RenderState> = None;
-    let mut game_over = false;
+    geo: &TableGeometry,
+    streams: &mut PeerStreams,
+) -> Received {
+    let mut received = Received::default();
     let mut buf = [0u8; 2048];
 
-    if let Some((ref p2p_sock, _)) = *p2p {
-        loop {
-            match p2p_sock.try_recv(&mut buf) {
-                Ok(n) if n > 0 && buf[0] == b'I' && n >= 9 => {
-                    let px = f32::from_le_bytes([buf[1], buf[2], buf[3], buf[4]]);
-                    let py = f32::from_le_bytes([buf[5], buf[6], buf[7], buf[8]]);
-                    if px.is_finite() && py.is_finite() {
-                        *opp_ptr = [px.clamp(PADDLE_RADIUS, TABLE_WIDTH - PADDLE_RADIUS), py];
+    for sock in std::iter::once(&ep.sock).chain(ep.v6.as_ref()) {
+        while let Ok((n, src)) = sock.try_recv_from(&mut buf) {
+            let src = SocketAddr::new(src.ip().to_canonical(), src.port());
+            let path = if Some(src) == ep.server { NetPath::Relay } else { NetPath::P2p };
+            let Some(data) = ep.incoming(&buf[..n], path == NetPath::Relay) else {
+                streams.reject(MessageType::Sealed, src);
+                continue;
+            };
+            let authentic = ep.key.get().is_none_or(|k| k.verify(&data).is_some());
+            let Ok(pkt) = protocol::decode(&data) else { continue };
+            let kind = pkt.header.kind;
+            // The server signs its commands too; only its handshake answer
+            // comes untagged
+            let from_server = path == NetPath::Relay && kind == MessageType::Handshake;
+            if !authentic && !from_server {
+                streams.reject(kind, src);
+                continue;
+            }
+            if path == NetPath::P2p && Some(src) != p2p && !matches!(kind, MessageType::Hello | MessageType::Ping) {
+                continue;
+            }
+            received.heard |= !matches!(kind, MessageType::Command | MessageType::Handshake);
+            match kind {
+                MessageType::Input => {
+                    let arrival = streams.input.record(pkt.header.seq);
+                    if arrival == Arrival::Duplicate { continue; }
+                    if let Some([px, py]) = protocol::decode_input(pkt.payload) {
+                        let ptr = [px.clamp(geo.paddle_radius, geo.width - geo.paddle_radius), py];
+                        received.inputs.push((pkt.header.seq, ptr));
+                        if arrival == Arrival::Newest { *opp_ptr = ptr; }
+                    }
+                }
+                MessageType::State => {
+                    if streams.state.record(pkt.header.seq) != Arrival::Newest { continue; }
+                    if let Ok(state) = bincode::deserialize(pkt.payload) {
+                        received.state = Some(state);
                     }
                 }
-                Ok(n) if n > 0 && buf[0] == b'S' => {
-                    if let Ok(state) = bincode::deserialize(&buf[1..n]) {
-                        received_state = Some(state);
+                MessageType::Command => {
+                    let cmd = pkt.command().unwrap_or_default();
+                    if cmd == "GAME_OVER" {
+                        received.game_over = true;
+                    } else if cmd == "OPPONENT_LEFT" {
+                        received.opponent_left = true;
+                    } else if let Some(list) = cmd.strip_prefix("PEER:").or_else(|| cmd.strip_prefix("CANDIDATES:")) {
+                        received.candidates.extend(parse_candidates(list));
+                    } else if let Some(salt) = cmd.strip_prefix("SALT:") {
+                        received.salt = Some(salt.to_string());
                     }
                 }
-                _ => break,
+                MessageType::Ping => {
+                    let pong = protocol::encode(MessageType::Pong, pkt.header.seq, &[]);
+                    let _ = sock.send_to(&ep.outgoing(&pong, path == NetPath::Relay), src).await;
+                }
+                MessageType::Pong => received.pongs.push((path, pkt.header.seq)),
+                MessageType::Hello if pkt.header.seq == traversal::PROBE => {
+                    let reply = protocol::encode(MessageType::Hello, traversal::PROBE_REPLY, &[]);
+                    let _ = sock.send_to(&ep.outgoing(&reply, path == NetPath::Relay), src).await;
+                    received.probes.push(src);
+                }
+                MessageType::Hello => received.probe_replies.push(src),
+                MessageType::Announce | MessageType::Handshake | MessageType::Sealed => {}
             }
         }
     }
 
-    loop {
-        match relay.try_recv(&mut buf) {
-            Ok(n) if n > 0 && buf[0] == b'I' && n >= 9 => {
-                let px = f32::from_le_bytes([buf[1], buf[2], buf[3], buf[4]]);
-                let py = f32::from_le_bytes([buf[5], buf[6], buf[7], buf[8]]);
-                if px.is_finite() && py.is_finite() {
-                    *opp_ptr = [px.clamp(PADDLE_RADIUS, TABLE_WIDTH - PADDLE_RADIUS), py];
-                }
-            }
-            Ok(n) if n > 0 && buf[0] == b'S' => {
-                if let Ok(state) = bincode::deserialize(&buf[1..n]) {
-                    received_state = Some(state);
-                }
-            }
-            Ok(n) if n > 0 => {
-                let txt = String::from_utf8_lossy(&buf[..n]);
-                if txt.trim() == "GAME_OVER" { game_over = true; }
-            }
-            _ => break,
+    received
+}
+
+/// Which peer simulates the puck: the one in whose half it is, with hysteresis
+/// at the midline so authority does not flip back and forth there. Around a
+/// rejoin someone must keep sending state: the rejoiner stays passive until it
+/// has one, so the peer that waited holds authority until the rejoiner's first
+/// state shows it has caught up.
+struct Authority {
+    is_host:  bool,
+    /// Whether we had authority last frame.
+    held:     bool,
+    /// Back from `rejoin_room`: passive until the opponent's state arrives.
+    resuming: bool,
+    /// The opponent just came back: authoritative until their state arrives.
+    covering: bool,
+}
+
+impl Authority {
+    fn new(is_host: bool, resume: bool) -> Self {
+        Self { is_host, held: is_host, resuming: resume, covering: false }
+    }
+
+    /// The opponent is heard again after going silent, and may have rejoined.
+    fn opponent_returned(&mut self) {
+        self.covering = true;
+    }
+
+    /// Whether we have authority this frame, with the puck at `puck_y` on a
+    /// table `th` high. A state `received` this frame is acted on from the
+    /// next, once the passive side has reconciled with it.
+    fn update(&mut self, puck_y: f32, th: f32, received: bool) -> bool {
+        let (mine, theirs) = if self.is_host {
+            (puck_y >= th / 2.0 + AUTH_HYSTERESIS, puck_y <= th / 2.0 - AUTH_HYSTERESIS)
+        } else {
+            (puck_y <= th / 2.0 - AUTH_HYSTERESIS, puck_y >= th / 2.0 + AUTH_HYSTERESIS)
+        };
+        self.held = if self.resuming {
+            false
+        } else if self.covering || mine {
+            true
+        } else if theirs {
+            false
+        } else {
+            self.held
+        };
+        if received {
+            self.resuming = false;
+            self.covering = false;
         }
+        self.held
     }
+}
 
-    (received_state, game_over)
+/// What a match loop runs with: the connection, our side of the table, the
+/// controls the UI drives it through and the channels it reports on.
+struct MatchContext {
+    ep:        Arc<Endpoint>,
+    is_host:   bool,
+    running:   Arc<AtomicBool>,
+    paused:    Arc<AtomicBool>,
+    pointer:   Arc<Mutex<[f32; 2]>>,
+    channel:   Channel<RenderState>,
+    stats:     Option<Channel<NetStats>>,
+    /// Token to re-register with the server under should our address change.
+    reconnect: Option<String>,
 }
 
 /// Each player runs this loop. The player in whose half the puck resides
 /// is authoritative for physics and sends the full RenderState. The other
 /// player receives that state and renders it. Supports P2P + relay fallback.
-async fn run_split_auth_game(
-    relay_sock: Arc<UdpSocket>,
-    p2p: Option<(Arc<UdpSocket>, SocketAddr)>,
-    running: Arc<AtomicBool>,
-    paused: Arc<AtomicBool>,
-    pointer: Arc<Mutex<[f32; 2]>>,
-    channel: Channel<RenderState>,
-    is_host: bool,
-    is_single_player: bool,
-) {
-    use puckduel_core::config::{TABLE_HEIGHT, WINNING_SCORE};
-
+/// The authoritative side simulates through a `RollbackSession`, so opponent
+/// inputs that arrive late still act on the frame they were made.
+/// If the opponent goes silent the match holds still for the reconnect grace
+/// period and is then won by forfeit. With `resume` set (after `rejoin_room`)
+/// we stay passive until the opponent's state tells us where the match is,
+/// while the opponent keeps authority until then.
+async fn run_split_auth_game(ctx: MatchContext, candidates: Vec<SocketAddr>, rules: MatchRules, started_at: Instant, resume: bool) {
+    let MatchContext { ep, is_host, running, paused, pointer, channel, stats, reconnect } = ctx;
     let mut gs = GameState::new();
-    let mut opp_ptr = [TABLE_WIDTH / 2.0, 120.0];
-    let mut was_authoritative = is_host;
+    gs.set_rules(rules);
+    gs.event_origin = if is_host { 0 } else { 1 };
+    let local = if is_host { 0 } else { 1 };
+    let mut opp_ptr = gs.geometry.paddle_start(1 - local);
     let dt = 1.0 / 60.0;
-    let mut ai_opponent = [TABLE_WIDTH / 2.0, 120.0];
 
     let mut interval = tokio::time::interval(Duration::from_secs_f64(1.0 / 60.0));
     interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    let mut streams = PeerStreams::default();
+    // Frames count from START on both peers, so input frame numbers line up
+    let current_frame = || (started_at.elapsed().as_secs_f64() * 60.0) as u32;
+    let mut frame = current_frame();
+    let mut session: Option<RollbackSession> = None;
+    let mut link = LinkMonitor::new();
+    let mut path = PeerPath::new(&candidates);
+    // Opponent states are rendered slightly in the past from this buffer
+    let clock = Instant::now();
+    let mut snapshots = SnapshotBuffer::new();
+    let mut smoother = StateSmoother::new();
+    let mut authority = Authority::new(is_host, resume);
+    let mut waited = false;
 
     while running.load(Ordering::Relaxed) {
         interval.tick().await;
@@ -258,103 +1036,244 @@ async fn run_split_auth_game(
         }
 
         let my_ptr = *pointer.lock().unwrap();
+        // One frame per tick, jumping ahead after skipped ticks or a pause
+        frame = (frame + 1).max(current_frame());
 
-        // Send my paddle via P2P + relay
-        {
-            let mut out = Vec::with_capacity(9);
-            out.push(b'I');
-            out.extend_from_slice(&my_ptr[0].to_le_bytes());
-            out.extend_from_slice(&my_ptr[1].to_le_bytes());
-            send_to_opponent(&out, &p2p, &relay_sock).await;
-        }
+        // Send my paddle via P2P + relay, tagged with the frame it applies to
+        send_to_opponent(&protocol::encode_input(frame, my_ptr), &ep, &path).await;
 
         // Receive from both
-        let (received_state, game_over) = recv_from_both(&p2p, &relay_sock, &mut opp_ptr).await;
-        if game_over {
+        let received = recv_from_both(&ep, path.addr(), &mut opp_ptr, &gs.geometry, &mut streams).await;
+        if received.game_over {
+            streams.log_loss();
             running.store(false, Ordering::Relaxed);
             return;
         }
-
-        // AI opponent
-        if is_single_player {
-            let p = &gs.puck;
-            if p.y < TABLE_HEIGHT / 2.0 || (p.vy < -30.0 && p.y < TABLE_HEIGHT * 0.6) {
-                let ttr = if p.vy.abs() > 1.0 { ((p.y - 120.0) / p.vy).abs().min(0.5) } else { 0.3 };
-                let tx = (p.x + p.vx * ttr).clamp(PADDLE_RADIUS, TABLE_WIDTH - PADDLE_RADIUS);
-                let ty = (p.y - 40.0).clamp(PADDLE_RADIUS, TABLE_HEIGHT / 2.0 - PADDLE_RADIUS);
-                let err = ((p.x * 1.7 + p.y * 3.1) * 100.0).sin() * 12.0;
-                ai_opponent[0] += (tx + err - ai_opponent[0]).clamp(-8.0, 8.0);
-                ai_opponent[1] += (ty - ai_opponent[1]).clamp(-6.0, 6.0);
+        if received.opponent_left {
+            log::info!("opponent left the match: won by forfeit");
+            gs.forfeit(local);
+            let _ = channel.send(gs.to_render());
+            streams.log_loss();
+            running.store(false, Ordering::Relaxed);
+            return;
+        }
+        // The opponent is back on a new connection, with a channel keyed afresh
+        if let Some(salt) = &received.salt {
+            ep.set_salt(salt);
+            ep.start_p2p(is_host);
+        }
+        for &(path, id) in &received.pongs {
+            link.pong(path, id);
+        }
+        if received.heard {
+            link.heard();
+        }
+        path.update(&received);
+        path.probe(&ep).await;
+        if link.ping(&ep, path.addr()).await {
+            if let Some(ch) = &stats {
+                let _ = ch.send(link.stats(false, path.is_healthy(), &streams));
             }
-            opp_ptr = ai_opponent;
         }
 
-        // Authority check with hysteresis to prevent rapid flipping at midline
-        let puck_in_my_half = if is_single_player {
-            true
-        } else if is_host {
-            if gs.puck.y >= TABLE_HEIGHT / 2.0 + AUTH_HYSTERESIS {
-                true
-            } else if gs.puck.y <= TABLE_HEIGHT / 2.0 - AUTH_HYSTERESIS {
-                false
-            } else {
-                was_authoritative
+        // Opponent silent: hold the match still until they rejoin or forfeit
+        if link.is_away() {
+            if let Some(token) = &reconnect {
+                link.rejoin(&ep, token).await;
             }
-        } else {
-            if gs.puck.y <= TABLE_HEIGHT / 2.0 - AUTH_HYSTERESIS {
-                true
-            } else if gs.puck.y >= TABLE_HEIGHT / 2.0 + AUTH_HYSTERESIS {
-                false
-            } else {
-                was_authoritative
+            if link.expired() {
+                log::info!("opponent did not return within {}s: won by forfeit", network::RECONNECT_GRACE_SECS);
+                gs.forfeit(local);
+                let _ = channel.send(gs.to_render());
+                let _ = ep.send(&protocol::encode_command("GAME_OVER")).await;
+                streams.log_loss();
+                running.store(false, Ordering::Relaxed);
+                return;
             }
-        };
-        was_authoritative = puck_in_my_half;
+            waited = true;
+            continue;
+        }
+
+        if std::mem::take(&mut waited) {
+            authority.opponent_returned();
+        }
+
+        let was_authoritative = authority.held;
+        let puck_in_my_half = authority.update(gs.puck.y, gs.geometry.height, received.state.is_some());
+        if puck_in_my_half != was_authoritative {
+            if puck_in_my_half {
+                snapshots.clear();
+            }
+            smoother.start_handoff();
+        }
 
         if puck_in_my_half {
-            // server_update takes (host_ptr, client_ptr) — host is always host player
-            let (h_ptr, c_ptr) = if is_host { (my_ptr, opp_ptr) } else { (opp_ptr, my_ptr) };
-            gs.server_update(dt, h_ptr, c_ptr);
+            // Simulate through the rollback session: my input lands this frame and
+            // late opponent inputs rewind and replay the frames they belong to
+            let session = session.get_or_insert_with(|| RollbackSession::new(local, gs.clone(), frame, dt));
+            for &(f, ptr) in &received.inputs {
+                session.add_remote_input(f, ptr);
+            }
+            if frame.saturating_sub(session.frame()) as usize > MAX_ROLLBACK_FRAMES {
+                *session = RollbackSession::new(local, session.state().clone(), frame, dt);
+            }
+            while session.frame() <= frame {
+                session.advance(my_ptr);
+            }
+            gs = session.state().clone();
             let state = gs.to_render();
 
-            if channel.send(state.clone()).is_err() { return; }
+            if channel.send(smoother.local(state.clone())).is_err() { return; }
 
+            // Sequenced by frame, so the numbering carries on across a rejoin
             if let Ok(encoded) = bincode::serialize(&state) {
-                let mut out = Vec::with_capacity(1 + encoded.len());
-                out.push(b'S');
-                out.extend_from_slice(&encoded);
-                send_to_opponent(&out, &p2p, &relay_sock).await;
+                send_to_opponent(&protocol::encode(MessageType::State, frame, &encoded), &ep, &path).await;
             }
 
-            if state.score[0] >= WINNING_SCORE || state.score[1] >= WINNING_SCORE {
-                let _ = relay_sock.send(b"GAME_OVER").await;
+            if state.game_over {
+                streams.log_loss();
+                let _ = ep.send(&protocol::encode_command("GAME_OVER")).await;
                 running.store(false, Ordering::Relaxed);
                 return;
             }
-        } else if let Some(state) = received_state {
-            gs.puck.x = state.puck[0];
-            gs.puck.y = state.puck[1];
-            gs.puck.vx = state.puck_vx;
-            gs.puck.vy = state.puck_vy;
-            gs.score = state.score;
-            if channel.send(state).is_err() { return; }
-            if gs.score[0] >= WINNING_SCORE || gs.score[1] >= WINNING_SCORE {
+        } else {
+            let now = clock.elapsed().as_secs_f64();
+            // Dead-reckon the puck, then pull it towards any authoritative update
+            session = None;
+            step_free_puck(&mut gs.puck, dt, &gs.geometry, &gs.params);
+            if let Some(state) = received.state {
+                reconcile_puck(&mut gs.puck, &state);
+                // Paddles and countdown too, so a rollback session started from
+                // `gs` at handoff sees the table as it is, not as we left it
+                gs.host_paddle = Paddle::new(state.host_paddle[0], state.host_paddle[1]);
+                gs.client_paddle = Paddle::new(state.client_paddle[0], state.client_paddle[1]);
+                gs.countdown = state.countdown;
+                gs.score = state.score;
+                // The authoritative peer's table, rules and match progress win
+                gs.geometry = state.geometry;
+                gs.params = state.params;
+                gs.rules = state.rules;
+                gs.clock = state.clock;
+                gs.sudden_death = state.sudden_death;
+                gs.game = state.game;
+                gs.games_won = state.games_won;
+                gs.winner = state.winner;
+                gs.forfeit = state.forfeit;
+                gs.game_over = state.game_over;
+                gs.merge_events(&state.events);
+                if gs.game_over {
+                    // Show the final state now rather than a buffered one
+                    let _ = channel.send(state);
+                    streams.log_loss();
+                    running.store(false, Ordering::Relaxed);
+                    return;
+                }
+                snapshots.push(now, state);
+            }
+            let mut target = snapshots.sample(now);
+            if snapshots.is_dry(now) {
+                // Nothing buffered to interpolate towards: show the dead-reckoned puck
+                if let Some(t) = target.as_mut() {
+                    t.puck = [gs.puck.x, gs.puck.y];
+                    t.puck_vx = gs.puck.vx;
+                    t.puck_vy = gs.puck.vy;
+                    t.puck_speed = gs.puck.speed();
+                }
+            }
+            if let Some(target) = target {
+                let opponent = if is_host { 1 } else { 0 };
+                if channel.send(smoother.remote(target, opponent)).is_err() { return; }
+            }
+        }
+    }
+    // Stopped mid-match: the server ends the room and tells the opponent
+    let _ = ep.send(&protocol::encode_command("LEAVE")).await;
+}
+
+/// Server-authoritative loop: the game server runs the physics, so we just
+/// send our pointer every tick and render the latest state it broadcasts.
+/// If the server goes quiet we keep trying to rejoin; once the grace period
+/// is over the server will have forfeited us, so the match ends as a loss.
+async fn run_server_auth_game(ctx: MatchContext) {
+    let MatchContext { ep, is_host, running, paused, pointer, channel, stats, reconnect } = ctx;
+    let geo = TableGeometry::default();
+    let mut link = LinkMonitor::new();
+    // Only the server sends to us, so no opponent input ever arrives here
+    let mut unused_ptr = [0.0; 2];
+
+    let mut interval = tokio::time::interval(Duration::from_secs_f64(1.0 / 60.0));
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    let mut streams = PeerStreams::default();
+    let mut input_seq = 0u32;
+    let mut last_state: Option<RenderState> = None;
+
+    while running.load(Ordering::Relaxed) {
+        interval.tick().await;
+
+        if !paused.load(Ordering::Relaxed) {
+            let my_ptr = *pointer.lock().unwrap();
+            input_seq = input_seq.wrapping_add(1);
+            let _ = ep.send(&protocol::encode_input(input_seq, my_ptr)).await;
+        }
+
+        let received = recv_from_both(&ep, None, &mut unused_ptr, &geo, &mut streams).await;
+        for &(path, id) in &received.pongs {
+            link.pong(path, id);
+        }
+        if received.heard {
+            link.heard();
+        }
+        if link.ping(&ep, None).await {
+            if let Some(ch) = &stats {
+                let _ = ch.send(link.stats(true, false, &streams));
+            }
+        }
+        if link.is_away() {
+            if let Some(token) = &reconnect {
+                link.rejoin(&ep, token).await;
+            }
+            if link.expired() {
+                log::info!("lost the game server for {}s: match forfeited", network::RECONNECT_GRACE_SECS);
+                if let Some(mut state) = last_state.take() {
+                    state.game_over = true;
+                    state.forfeit = true;
+                    state.winner = Some(if is_host { 1 } else { 0 });
+                    let _ = channel.send(state);
+                }
                 running.store(false, Ordering::Relaxed);
                 return;
             }
         }
+        if let Some(state) = received.state {
+            last_state = Some(state.clone());
+            if channel.send(state).is_err() { return; }
+        }
+        if received.game_over {
+            streams.log_loss();
+            running.store(false, Ordering::Relaxed);
+            return;
+        }
     }
+    // Stopped mid-match: the server forfeits us rather than waiting out the grace
+    let _ = ep.send(&protocol::encode_command("LEAVE")).await;
 }
 
-/// Start the game loop: split-authority + P2P (with relay fallback).
+/// Start the game loop: split-authority + P2P (with relay fallback), or the
+/// server-authoritative loop when the room was started with `START_AUTH`.
+/// `rules` is decided by the host; the joiner adopts the host's rules from the
+/// first authoritative state it receives. Matches the server runs (solo games
+/// included) were given theirs in `create_room` or `create_solo`. `stats` receives
+/// a `NetStats` about twice a second for the connection indicator and the
+/// reconnect countdown.
 #[tauri::command]
 pub async fn start_game(
     engine: State<'_, GameEngine>,
     server: State<'_, ServerState>,
     is_host: bool,
-    is_single_player: bool,
     start_received: bool,
+    rules: Option<MatchRules>,
     channel: Channel<RenderState>,
+    stats: Option<Channel<NetStats>>,
 ) -> Result<(), String> {
     {
         let old = engine.task.lock().unwrap().take();
@@ -362,27 +1281,30 @@ pub async fn start_game(
     }
     engine.running.store(false, Ordering::SeqCst);
     engine.paused.store(false, Ordering::SeqCst);
-    *engine.pointer.lock().unwrap() = if is_host { [TABLE_WIDTH / 2.0, TABLE_HEIGHT - 120.0] } else { [TABLE_WIDTH / 2.0, 120.0] };
+    *engine.pointer.lock().unwrap() = TableGeometry::default().paddle_start(if is_host { 0 } else { 1 });
     engine.running.store(true, Ordering::SeqCst);
 
     let running = engine.running.clone();
     let paused = engine.paused.clone();
     let pointer = engine.pointer.clone();
 
-    let relay_sock = server.socket.lock().await.take().ok_or("not connected to server")?;
+    let ep = server.socket.lock().await.take().ok_or("not connected to server")?;
     let _room_code = server.room_code.lock().await.take().unwrap_or_default();
-    let peer_addr_str = server.peer_addr.lock().await.take();
+    let candidates = std::mem::take(&mut *server.peer_candidates.lock().await);
+    let started_at = server.started_at.lock().await.take().unwrap_or_else(Instant::now);
+    let reconnect = server.reconnect.lock().await.take();
+    let resume = std::mem::take(&mut *server.resume.lock().await);
+    ep.start_p2p(is_host);
+    let ctx = MatchContext { ep, is_host, running, paused, pointer, channel, stats, reconnect };
 
-    // Try P2P hole-punching (2s timeout)
-    let p2p = if let Some(ref addr) = peer_addr_str {
-        try_p2p(addr, 2).await
-    } else {
-        None
-    };
+    if std::mem::take(&mut *server.authoritative.lock().await) {
+        let handle = tokio::spawn(run_server_auth_game(ctx));
+        *engine.task.lock().unwrap() = Some(handle);
+        return Ok(());
+    }
 
-    let handle = tokio::spawn(async move {
-        run_split_auth_game(relay_sock, p2p, running, paused, pointer, channel, is_host, is_single_player).await;
-    });
+    let rules = rules.unwrap_or_default();
+    let handle = tokio::spawn(run_split_auth_game(ctx, candidates, rules, started_at, resume));
 
     *engine.task.lock().unwrap() = Some(handle);
 
@@ -391,11 +1313,10 @@ pub async fn start_game(
 
 #[tauri::command]
 pub fn stop_game(engine: State<'_, GameEngine>) {
+    // The loop sees `running` drop within a tick and sends LEAVE on its way
+    // out; `start_game` aborts it if it is somehow still there
     engine.running.store(false, Ordering::SeqCst);
     engine.paused.store(false, Ordering::SeqCst);
-    if let Some(h) = engine.task.lock().unwrap().take() {
-        h.abort();
-    }
 }
 
 #[tauri::command]
@@ -412,3 +1333,53 @@ pub fn resume_game(engine: State<'_, GameEngine>) {
 pub fn set_pointer(engine: State<'_, GameEngine>, x: f32, y: f32) {
     *engine.pointer.lock().unwrap() = [x, y];
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_someone_keeps_authority_across_a_rejoin() {
+        let th = TABLE_HEIGHT;
+        // The puck sits in the joiner's half when the joiner drops out
+        let joiners_half = th / 4.0;
+        let mut host = Authority::new(true, false);
+        assert!(!host.update(joiners_half, th, false));
+
+        // The joiner rejoins and waits for a state; the host sends them one
+        let mut joiner = Authority::new(false, true);
+        host.opponent_returned();
+        assert!(!joiner.update(joiners_half, th, false));
+        assert!(host.update(joiners_half, th, false));
+
+        // Caught up, the joiner takes the puck in its half back, and the
+        // host lets go once the joiner's state reaches it
+        assert!(!joiner.update(joiners_half, th, true));
+        assert!(joiner.update(joiners_half, th, false));
+        assert!(host.update(joiners_half, th, true));
+        assert!(!host.update(joiners_half, th, false));
+    }
+
+    #[test]
+    fn test_lan_announce_round_trips() {
+        let src: SocketAddr = "192.168.1.20:40000".parse().unwrap();
+        let (name, packet) = lan_announce("  Living room  ");
+        assert_eq!(name, "Living room");
+        let game = parse_lan_announce(&packet, src).unwrap();
+        assert_eq!(game.name, "Living room");
+        assert_eq!(game.addr, "192.168.1.20:40000");
+
+        assert_eq!(lan_announce("   ").0, "Puck Duel");
+        assert_eq!(lan_announce(&"x".repeat(100)).0.chars().count(), network::LAN_NAME_MAX_CHARS);
+    }
+
+    #[test]
+    fn test_lan_announce_ignores_other_packets_and_versions() {
+        let src: SocketAddr = "192.168.1.20:40000".parse().unwrap();
+        assert!(parse_lan_announce(&protocol::encode_command("CREATE"), src).is_none());
+        let (_, mut packet) = lan_announce("Old build");
+        packet[2..6].copy_from_slice(&(network::PROTOCOL_VERSION - 1).to_le_bytes());
+        assert!(parse_lan_announce(&packet, src).is_none());
+        assert!(parse_lan_announce(b"hello", src).is_none());
+    }
+}
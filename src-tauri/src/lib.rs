@@ -2,7 +2,7 @@ mod config;
 mod physics;
 mod game;
 
-use game::{GameEngine, ServerState, create_room, join_room, create_solo, wait_for_opponent, start_game, stop_game, pause_game, resume_game, set_pointer};
+use game::{GameEngine, ServerState, create_room, join_room, create_solo, wait_for_opponent, rejoin_room, host_lan, discover_lan, join_lan, start_game, stop_game, pause_game, resume_game, set_pointer};
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
@@ -12,7 +12,7 @@ pub fn run() {
         .manage(ServerState::new())
         .manage(GameEngine::new())
         .invoke_handler(tauri::generate_handler![
-            create_room, join_room, create_solo, wait_for_opponent, start_game, stop_game, pause_game, resume_game, set_pointer,
+            create_room, join_room, create_solo, wait_for_opponent, rejoin_room, host_lan, discover_lan, join_lan, start_game, stop_game, pause_game, resume_game, set_pointer,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");